@@ -0,0 +1,136 @@
+// Command line tool used to inspect manifests without writing any Rust.
+
+use std::{fs::File, io::{self, BufReader, Read, Write}, process::ExitCode};
+
+use epic_manifest_parser_rs::manifest::{FManifest, FManifestParser};
+
+const USAGE: &str = "Usage: epic-manifest <command> <manifest>
+
+Commands:
+    info      Print a summary of the header and the metadata
+    files     List the files of the build with their size and install tags
+    chunks    List the chunks of the build
+    json      Dump the whole manifest as JSON, this is not a legacy JSON manifest and can't be read back by this tool
+
+<manifest> can be a binary or a JSON manifest, use - to read it from stdin.";
+
+fn read_manifest(path: &str) -> Result<FManifest, Box<dyn std::error::Error>> {
+    let source: Box<dyn Read> = if path == "-" {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(File::open(path)?)
+    };
+
+    Ok(FManifestParser::from_reader(BufReader::new(source)).parse()?)
+}
+
+fn info(out: &mut impl Write, manifest: &FManifest) -> io::Result<()> {
+    let meta = &manifest.meta;
+    let header = &manifest.header;
+
+    writeln!(out, "App name:        {}", meta.app_name())?;
+    writeln!(out, "App ID:          {}", meta.app_id())?;
+    writeln!(out, "Build version:   {}", meta.build_version())?;
+    match meta.build_id() {
+        Some(build_id) => writeln!(out, "Build ID:        {}", build_id)?,
+        None => writeln!(out, "Build ID:        {} (computed)", meta.backwards_compatible_build_id())?,
+    }
+    writeln!(out, "Launch exe:      {}", meta.launch_exe())?;
+    writeln!(out, "Launch command:  {}", meta.launch_command())?;
+    writeln!(out, "Feature level:   {:?} ({})", header.version(), header.version().to_i32())?;
+    writeln!(out, "Stored as:       {:?}", header.stored_as())?;
+    writeln!(out, "Data size:       {} bytes ({} bytes stored)", header.data_size_uncompressed(), header.data_size_compressed())?;
+    writeln!(out, "SHA1:            {}", header.sha_hash().to_hex_string())?;
+    writeln!(out, "File data:       {}", meta.is_file_data())?;
+
+    let install_size: u64 = manifest.file_list.entries().iter().map(|file| file.file_size()).sum();
+    let download_size: u64 = manifest.chunk_list.chunks().iter().map(|chunk| chunk.compressed_size().max(0) as u64).sum();
+    writeln!(out, "Files:           {} ({} bytes)", manifest.file_list.entries().len(), install_size)?;
+    writeln!(out, "Chunks:          {} ({} bytes to download)", manifest.chunk_list.chunks().len(), download_size)?;
+
+    if !meta.prereq_name().is_empty() || !meta.prereq_ids().is_empty() {
+        writeln!(out, "Prerequisites:   {} {} {}", meta.prereq_name(), meta.prereq_path(), meta.prereq_args())?;
+        writeln!(out, "Prerequisite IDs: {}", meta.prereq_ids().join(", "))?;
+    }
+
+    if !manifest.custom_fields.fields.is_empty() {
+        writeln!(out, "Custom fields:")?;
+        for (key, value) in manifest.custom_fields.fields.iter() {
+            writeln!(out, "    {}: {}", key, value)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn files(out: &mut impl Write, manifest: &FManifest) -> io::Result<()> {
+    for file in manifest.file_list.entries() {
+        let tags = if file.install_tags().is_empty() {
+            String::new()
+        } else {
+            format!("  [{}]", file.install_tags().join(", "))
+        };
+
+        writeln!(out, "{:>14}  {}  {}{}", file.file_size(), file.hash().to_hex_string(), file.filename(), tags)?;
+    }
+
+    Ok(())
+}
+
+fn chunks(out: &mut impl Write, manifest: &FManifest) -> io::Result<()> {
+    for chunk in manifest.chunk_list.chunks() {
+        writeln!(
+            out,
+            "{}  {}  {}  {:>10}  {:>10}  {}",
+            chunk.guid().to_string(),
+            chunk.hash_str(),
+            chunk.group_num_str(),
+            chunk.uncompressed_size(),
+            chunk.compressed_size(),
+            chunk.sha_hash().to_hex_string()
+        )?;
+    }
+
+    Ok(())
+}
+
+fn run(command: &str, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let manifest = read_manifest(path)?;
+    let mut out = io::stdout().lock();
+
+    let result = match command {
+        "info" => info(&mut out, &manifest),
+        "files" => files(&mut out, &manifest),
+        "chunks" => chunks(&mut out, &manifest),
+        "json" => writeln!(out, "{}", serde_json::to_string_pretty(&manifest)?),
+        _ => unreachable!(),
+    };
+
+    // the output being piped into something like `head` is not an error
+    match result {
+        Err(error) if error.kind() != io::ErrorKind::BrokenPipe => Err(error.into()),
+        _ => Ok(()),
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match args.as_slice() {
+        [command, path] if ["info", "files", "chunks", "json"].contains(&command.as_str()) => match run(command, path) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(error) => {
+                eprintln!("epic-manifest: {}", error);
+                ExitCode::FAILURE
+            }
+        },
+        [flag] if flag == "-h" || flag == "--help" => {
+            println!("{}", USAGE);
+            ExitCode::SUCCESS
+        },
+        _ => {
+            eprintln!("{}", USAGE);
+            ExitCode::FAILURE
+        }
+    }
+}
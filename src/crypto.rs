@@ -0,0 +1,180 @@
+use aes::{cipher::{generic_array::GenericArray, BlockDecrypt, KeyInit}, Aes256};
+
+use crate::{error::ParseError, helper, ParseResult};
+
+pub const AES_BLOCK_SIZE: usize = 16;
+pub const AES_KEY_SIZE: usize = 32;
+
+/// This trait is implemented to decrypt manifests and chunks stored with the `Encrypted` flag.
+/// Decryption happens before decompression, the returned data may keep the padding added by the cipher.
+pub trait Decryptor: Send + Sync {
+    fn decrypt(&self, data: &[u8]) -> ParseResult<Vec<u8>>;
+}
+
+/// This type decrypts data the same way `FAES` does in the Unreal Engine: AES-256 in ECB mode, without any padding removal
+#[derive(Clone)]
+pub struct AesDecryptor {
+    cipher: Aes256,
+}
+
+impl AesDecryptor {
+    pub fn new(key: [u8; AES_KEY_SIZE]) -> AesDecryptor {
+        AesDecryptor {
+            cipher: Aes256::new(&GenericArray::from(key)),
+        }
+    }
+
+    /// This function is used to create a decryptor from a hexadecimal key, with or without the `0x` prefix
+    pub fn from_hex(key: &str) -> ParseResult<AesDecryptor> {
+        let key = key.strip_prefix("0x").unwrap_or(key);
+        let key: [u8; AES_KEY_SIZE] = helper::from_hex(key)?.try_into().map_err(|_| ParseError::InvalidData)?;
+
+        Ok(AesDecryptor::new(key))
+    }
+}
+
+impl std::fmt::Debug for AesDecryptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // the key is not printed
+        f.debug_struct("AesDecryptor").finish_non_exhaustive()
+    }
+}
+
+impl Decryptor for AesDecryptor {
+    fn decrypt(&self, data: &[u8]) -> ParseResult<Vec<u8>> {
+        if !data.len().is_multiple_of(AES_BLOCK_SIZE) {
+            return Err(ParseError::DecryptionError);
+        }
+
+        let mut buffer = data.to_vec();
+        for block in buffer.chunks_exact_mut(AES_BLOCK_SIZE) {
+            self.cipher.decrypt_block(GenericArray::from_mut_slice(block));
+        }
+
+        Ok(buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use aes::cipher::BlockEncrypt;
+
+    use crate::{manifest::{builder::ManifestBuilder, chunks::{chunk::FChunk, chunk_header::FChunkHeader}, shared::{EChunkStorageFlags, EManifestStorageFlags, FGuid}, FManifestParser, ManifestWriter}, reader::ByteReader, test_utils::{random_data, TempDir}, writer::ByteWriter};
+
+    use super::*;
+
+    const KEY: [u8; AES_KEY_SIZE] = [0x42; AES_KEY_SIZE];
+
+    // the cipher pads the data with zeroes to its block size
+    fn encrypt(data: &[u8]) -> Vec<u8> {
+        let cipher = Aes256::new(&GenericArray::from(KEY));
+        let mut buffer = data.to_vec();
+        buffer.resize(data.len().next_multiple_of(AES_BLOCK_SIZE), 0);
+
+        for block in buffer.chunks_exact_mut(AES_BLOCK_SIZE) {
+            cipher.encrypt_block(GenericArray::from_mut_slice(block));
+        }
+
+        buffer
+    }
+
+    #[test]
+    fn aes_256_ecb_known_answer() {
+        // FIPS-197 appendix C.3
+        let decryptor = AesDecryptor::from_hex("0x000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f").unwrap();
+        let ciphertext = helper::from_hex("8ea2b7ca516745bfeafc49904b4960898ea2b7ca516745bfeafc49904b496089").unwrap();
+
+        assert_eq!(decryptor.decrypt(&ciphertext).unwrap(), helper::from_hex("00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff").unwrap());
+        assert!(matches!(decryptor.decrypt(&ciphertext[..20]), Err(ParseError::DecryptionError)));
+    }
+
+    fn encrypt_manifest(stored_as: EManifestStorageFlags) -> (Vec<u8>, Vec<u8>) {
+        let source = TempDir::new("crypto_manifest_source");
+        let cloud_dir = TempDir::new("crypto_manifest_cloud");
+        source.write("Game.exe", &random_data(3000, 8));
+
+        let mut manifest = ManifestBuilder::new("Test", "1.0").build(source.path(), cloud_dir.path()).unwrap();
+        manifest.header.stored_as = stored_as;
+        let data = ManifestWriter::new(&manifest).write().unwrap();
+
+        // the header is followed by the stored data, its size is at offset 12 and the storage flags at offset 36
+        let header_size = manifest.header.header_size() as usize;
+        let mut encrypted = data[..header_size].to_vec();
+        encrypted.extend(encrypt(&data[header_size..]));
+        let stored_size = (encrypted.len() - header_size) as u32;
+        encrypted[12..16].copy_from_slice(&stored_size.to_le_bytes());
+        encrypted[36] = (stored_as | EManifestStorageFlags::Encrypted).bits();
+
+        (data, encrypted)
+    }
+
+    fn assert_manifest_is_decrypted(stored_as: EManifestStorageFlags) {
+        let (data, encrypted) = encrypt_manifest(stored_as);
+
+        assert!(matches!(FManifestParser::new(&encrypted).parse(), Err(ParseError::MissingDecryptor)));
+
+        let mut manifest = FManifestParser::new(&encrypted).with_decryptor(AesDecryptor::new(KEY)).parse().unwrap();
+        assert!(manifest.header.stored_as().is_encrypted());
+        assert_eq!(manifest.header.stored_as().is_compressed(), stored_as.is_compressed());
+
+        manifest.header.stored_as = stored_as;
+        assert_eq!(ManifestWriter::new(&manifest).write().unwrap(), data);
+    }
+
+    #[test]
+    fn encrypted_manifests_are_decrypted() {
+        assert_manifest_is_decrypted(EManifestStorageFlags::empty());
+    }
+
+    #[test]
+    fn encrypted_compressed_manifests_are_decrypted_then_decompressed() {
+        assert_manifest_is_decrypted(EManifestStorageFlags::Compressed);
+    }
+
+    fn encrypt_chunk(data: Vec<u8>, compress: bool) -> Vec<u8> {
+        let chunk_file = FChunk::new(FGuid { a: 1, b: 2, c: 3, d: 4 }, data).to_bytes(compress).unwrap();
+        let header = FChunkHeader::parse(&mut ByteReader::from_slice(&chunk_file)).unwrap();
+        assert_eq!(header.is_compressed(), compress);
+
+        let stored_data = encrypt(&chunk_file[header.header_size() as usize..]);
+        let header = FChunkHeader::new(
+            header.guid(),
+            header.rolling_hash(),
+            header.sha_hash().unwrap(),
+            header.stored_as() | EChunkStorageFlags::Encrypted,
+            stored_data.len() as u32,
+            header.data_size_uncompressed().unwrap()
+        );
+
+        let mut writer = ByteWriter::new();
+        header.write(&mut writer);
+        writer.write_bytes(&stored_data);
+        writer.into_inner()
+    }
+
+    #[test]
+    fn encrypted_chunks_are_decrypted_and_their_padding_is_removed() {
+        // 1000 bytes are padded to 1008
+        let data = random_data(1000, 9);
+        let chunk_file = encrypt_chunk(data.clone(), false);
+
+        assert!(matches!(FChunk::from_bytes(&chunk_file), Err(ParseError::MissingDecryptor)));
+
+        let chunk = FChunk::parse_with_decryptor(&mut ByteReader::from_slice(&chunk_file), Some(&AesDecryptor::new(KEY))).unwrap();
+        assert_eq!(chunk.header().data_size_compressed(), 1008);
+        assert_eq!(chunk.data(), data);
+    }
+
+    #[test]
+    fn encrypted_compressed_chunks_are_decrypted_then_decompressed() {
+        let data = random_data(100, 10).repeat(10);
+        let chunk_file = encrypt_chunk(data.clone(), true);
+
+        let chunk = FChunk::parse_with_decryptor(&mut ByteReader::from_slice(&chunk_file), Some(&AesDecryptor::new(KEY))).unwrap();
+        assert!(chunk.header().is_compressed() && chunk.header().is_encrypted());
+        assert_eq!(chunk.data(), data);
+
+        let wrong_key = AesDecryptor::new([0x24; AES_KEY_SIZE]);
+        assert!(FChunk::parse_with_decryptor(&mut ByteReader::from_slice(&chunk_file), Some(&wrong_key)).is_err());
+    }
+}
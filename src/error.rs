@@ -1,15 +1,51 @@
 
-#[derive(Debug)]
+use crate::manifest::shared::FGuid;
+
+#[derive(Debug, Clone)]
 pub enum ParseError {
     InvalidMagic,
     InvalidData,
+    InvalidJson,
     InvalidDigest,
     InvalidStorageFlag,
     OffsetMismatch,
     DecompressionError,
+    CompressionError,
     HashMismatch,
     SizeMismatch,
-    Overflow
+    Overflow,
+    IoError(std::io::ErrorKind),
+    /// A chunk referenced by the manifest could not be found
+    MissingChunk(FGuid),
+    /// A path from the manifest would be written outside of the install directory
+    InvalidPath(String),
+    /// Unknown data was found after the last section of the manifest
+    TrailingData {
+        offset: usize,
+        size: usize
+    },
+    /// The data is encrypted and no decryptor was given
+    MissingDecryptor,
+    DecryptionError,
+    /// A file could not be downloaded, `status` is the HTTP status when the server answered
+    DownloadError {
+        url: String,
+        status: Option<u16>
+    },
+    /// A section of the binary did not end where its stored size says it should.
+    /// This usually means the section was written with a data version this crate doesn't know about.
+    SectionSizeMismatch {
+        /// Name of the type that failed to parse, e.g. `FChunkList`
+        section: &'static str,
+        /// Offset of the beginning of the section in the data being parsed
+        offset: usize,
+        /// Size stored in the section
+        expected: usize,
+        /// Amount of bytes actually read
+        actual: usize,
+        /// Data version of the section, `None` for structures that aren't versioned
+        data_version: Option<u32>
+    }
 }
 
 impl std::fmt::Display for ParseError {
@@ -17,16 +53,41 @@ impl std::fmt::Display for ParseError {
         match self {
             ParseError::InvalidMagic => write!(f, "Invalid magic"),
             ParseError::InvalidData => write!(f, "Invalid data"),
+            ParseError::InvalidJson => write!(f, "Invalid JSON manifest"),
             ParseError::InvalidDigest => write!(f, "Invalid digest"),
             ParseError::Overflow => write!(f, "Overflow"),
             ParseError::InvalidStorageFlag => write!(f, "Invalid storage flag"),
             ParseError::OffsetMismatch => write!(f, "Offset mismatch"),
             ParseError::DecompressionError => write!(f, "Decompression failed"),
+            ParseError::CompressionError => write!(f, "Compression failed"),
             ParseError::HashMismatch => write!(f, "Hash does not match"),
             ParseError::SizeMismatch => write!(f, "Sizes does not match"),
+            ParseError::IoError(kind) => write!(f, "I/O error: {}", kind),
+            ParseError::MissingChunk(guid) => write!(f, "Missing chunk {}", guid.to_string()),
+            ParseError::InvalidPath(path) => write!(f, "Invalid path: {}", path),
+            ParseError::TrailingData { offset, size } => write!(f, "{} bytes of unknown data at offset {}", size, offset),
+            ParseError::MissingDecryptor => write!(f, "Data is encrypted but no decryptor was given"),
+            ParseError::DecryptionError => write!(f, "Decryption failed"),
+            ParseError::DownloadError { url, status: Some(status) } => write!(f, "Failed to download {}: HTTP {}", url, status),
+            ParseError::DownloadError { url, status: None } => write!(f, "Failed to download {}", url),
+            ParseError::SectionSizeMismatch { section, offset, expected, actual, data_version } => {
+                write!(f, "{} size mismatch at offset {}: expected {} bytes but read {}", section, offset, expected, actual)?;
+
+                if let Some(data_version) = data_version {
+                    write!(f, " (data version {})", data_version)?;
+                }
+
+                Ok(())
+            },
             
         }
     }
 }
 
-impl std::error::Error for ParseError {}
\ No newline at end of file
+impl std::error::Error for ParseError {}
+
+impl From<std::io::Error> for ParseError {
+    fn from(error: std::io::Error) -> Self {
+        ParseError::IoError(error.kind())
+    }
+}
\ No newline at end of file
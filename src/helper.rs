@@ -1,5 +1,7 @@
 use std::fmt::LowerHex;
 
+use crate::{error::ParseError, ParseResult};
+
 
 
 
@@ -26,4 +28,16 @@ where
     }
 
     result
+}
+
+/// Decodes a string of hexadecimal characters into bytes
+pub fn from_hex(hex: &str) -> ParseResult<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return Err(ParseError::InvalidData);
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| ParseError::InvalidData))
+        .collect()
 }
\ No newline at end of file
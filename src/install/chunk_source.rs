@@ -0,0 +1,50 @@
+use std::io::{Read, Seek};
+
+use crate::{error::ParseError, manifest::{chunk_info::FChunkInfo, chunks::{chunk::FChunk, chunk_database::ChunkDatabase}, shared::FSHAHash}, rolling_hash::FRollingHash, ParseResult};
+
+/// This trait is implemented by everything the installer can obtain chunks from: a local directory (ChunkStore),
+/// a chunk database (ChunkDatabase) or the CDN (HttpChunkSource, with the `http` feature).
+/// Returned chunks are decompressed and verified against the FChunkInfo they were requested with.
+pub trait ChunkSource: Send + Sync {
+    fn fetch_chunk(&self, chunk: &FChunkInfo) -> ParseResult<FChunk>;
+
+    /// This function is used to know if the chunk can be fetched without trying to, sources that can't tell return true
+    fn has_chunk(&self, chunk: &FChunkInfo) -> bool;
+}
+
+impl<R: Read + Seek + Send> ChunkSource for ChunkDatabase<R> {
+    fn fetch_chunk(&self, chunk: &FChunkInfo) -> ParseResult<FChunk> {
+        let fetched_chunk = self.read_chunk(chunk.guid())?;
+        verify_chunk(chunk, &fetched_chunk)?;
+
+        Ok(fetched_chunk)
+    }
+
+    fn has_chunk(&self, chunk: &FChunkInfo) -> bool {
+        self.contains(chunk.guid())
+    }
+}
+
+/// This function is used to check that a chunk is the one described by a FChunkInfo of the manifest
+pub fn verify_chunk(info: &FChunkInfo, chunk: &FChunk) -> ParseResult<()> {
+    if chunk.header().guid() != *info.guid() {
+        return Err(ParseError::InvalidData);
+    }
+
+    if !is_chunk_data_valid(info, chunk.data()) {
+        return Err(ParseError::HashMismatch);
+    }
+
+    Ok(())
+}
+
+/// This function is used to check the data of a chunk against the SHA1 and rolling hash of its FChunkInfo.
+/// Old manifests may only store one of the two hashes, a chunk without any hash can't be trusted.
+pub(crate) fn is_chunk_data_valid(chunk: &FChunkInfo, data: &[u8]) -> bool {
+    let has_sha_hash = *chunk.sha_hash() != FSHAHash::default();
+    let has_rolling_hash = chunk.hash() != 0;
+
+    (has_sha_hash || has_rolling_hash)
+        && (!has_sha_hash || FSHAHash::new_from_hashable(data) == *chunk.sha_hash())
+        && (!has_rolling_hash || FRollingHash::get_hash_for_data_set(data) == chunk.hash())
+}
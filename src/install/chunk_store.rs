@@ -0,0 +1,134 @@
+use std::{collections::HashMap, fs, io::{Read, Seek}, path::{Path, PathBuf}, sync::Arc};
+
+use crate::{crypto::Decryptor, error::ParseError, manifest::{chunk_info::FChunkInfo, chunks::{chunk::FChunk, chunk_database::ChunkDatabase}, shared::FGuid}, reader::ByteReader, ParseResult};
+
+use super::chunk_source::{verify_chunk, ChunkSource};
+
+pub const CHUNK_EXTENSION: &str = "chunk";
+
+/// This type represents a local directory of downloaded chunk files.
+/// Chunks are found by the GUID at the end of their filename, so flat directories (`{GUID}.chunk`)
+/// as well as CDN layouts (`ChunksV4/12/{HASH}_{GUID}.chunk`) can be used.
+#[derive(Clone, Default)]
+pub struct ChunkStore {
+    root: PathBuf,
+    chunks: HashMap<FGuid, PathBuf>,
+    decryptor: Option<Arc<dyn Decryptor>>,
+}
+
+impl std::fmt::Debug for ChunkStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChunkStore")
+            .field("root", &self.root)
+            .field("chunks", &self.chunks)
+            .field("decryptor", &self.decryptor.is_some())
+            .finish()
+    }
+}
+
+impl ChunkStore {
+    /// This function is used to index every chunk file found in `root` and its subdirectories
+    pub fn open(root: impl AsRef<Path>) -> ParseResult<ChunkStore> {
+        let root = root.as_ref().to_path_buf();
+        let mut chunks = HashMap::new();
+        let mut directories = vec![root.clone()];
+
+        while let Some(directory) = directories.pop() {
+            for entry in fs::read_dir(&directory)? {
+                let path = entry?.path();
+
+                if path.is_dir() {
+                    directories.push(path);
+                } else if let Some(guid) = ChunkStore::guid_from_path(&path) {
+                    chunks.insert(guid, path);
+                }
+            }
+        }
+
+        Ok(ChunkStore {
+            root,
+            chunks,
+            decryptor: None,
+        })
+    }
+
+    /// This function is used to write every chunk of a chunk database into `directory` as `{GUID}.chunk` files.
+    /// The returned ChunkStore can be used to install the files of a manifest.
+    pub fn extract<R: Read + Seek>(database: &ChunkDatabase<R>, directory: impl AsRef<Path>) -> ParseResult<ChunkStore> {
+        let directory = directory.as_ref();
+        fs::create_dir_all(directory)?;
+
+        for location in database.header().contents() {
+            let data = database.read_chunk_file(location.chunk_id())?;
+            fs::write(directory.join(format!("{}.{}", location.chunk_id().to_string(), CHUNK_EXTENSION)), data)?;
+        }
+
+        ChunkStore::open(directory)
+    }
+
+    /// This function is used to get the GUID of a chunk from its filename, which ends with the 32 hexadecimal characters of the GUID
+    pub fn guid_from_path(path: &Path) -> Option<FGuid> {
+        if path.extension()? != CHUNK_EXTENSION {
+            return None;
+        }
+
+        let stem = path.file_stem()?.to_str()?;
+        stem.get(stem.len().checked_sub(32)?..)?.parse().ok()
+    }
+
+    /// This function is used to set the decryptor used to read encrypted chunks
+    pub fn set_decryptor(&mut self, decryptor: impl Decryptor + 'static) {
+        self.decryptor = Some(Arc::new(decryptor));
+    }
+
+    /// This function is used to add a chunk file that was written after the store was opened
+    pub fn insert(&mut self, guid: FGuid, path: PathBuf) {
+        self.chunks.insert(guid, path);
+    }
+
+    pub fn contains(&self, guid: &FGuid) -> bool {
+        self.chunks.contains_key(guid)
+    }
+
+    pub fn path(&self, guid: &FGuid) -> Option<&Path> {
+        self.chunks.get(guid).map(|path| path.as_path())
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    /// This function is used to read, decompress and verify a chunk
+    pub fn read_chunk(&self, guid: &FGuid) -> ParseResult<FChunk> {
+        let path = self.path(guid).ok_or(ParseError::MissingChunk(*guid))?;
+        let data = fs::read(path)?;
+        let chunk = FChunk::parse_with_decryptor(&mut ByteReader::from_slice(&data), self.decryptor.as_deref())?;
+
+        if chunk.header().guid() != *guid {
+            return Err(ParseError::InvalidData);
+        }
+
+        Ok(chunk)
+    }
+}
+
+impl ChunkSource for ChunkStore {
+    fn fetch_chunk(&self, chunk: &FChunkInfo) -> ParseResult<FChunk> {
+        let fetched_chunk = self.read_chunk(chunk.guid())?;
+        verify_chunk(chunk, &fetched_chunk)?;
+
+        Ok(fetched_chunk)
+    }
+
+    fn has_chunk(&self, chunk: &FChunkInfo) -> bool {
+        self.contains(chunk.guid())
+    }
+}
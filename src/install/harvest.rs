@@ -0,0 +1,160 @@
+use std::{collections::HashMap, fs::{self, File}, io::{ErrorKind, Read, Seek, SeekFrom}, path::{Path, PathBuf}};
+
+use crate::{error::ParseError, manifest::{chunk_info::{chunk_path, FChunkInfo}, chunks::chunk::FChunk, shared::FGuid, FManifest}, ParseResult};
+
+use super::{chunk_source::is_chunk_data_valid, chunk_store::ChunkStore, install_path, patch::{index_chunk_parts, PartLocation}};
+
+/// This type is used to rebuild the chunks of a build from an existing install of it, when the chunks themselves are no longer available.
+/// Every piece of a chunk is read from the installed files using the chunk parts of the manifest, bytes that no file holds are left to zero
+/// as they are usually the padding of the chunk. A chunk is only kept if it matches the hashes of its FChunkInfo.
+pub struct ChunkHarvester<'a> {
+    manifest: &'a FManifest,
+    install_dir: PathBuf,
+    parts: HashMap<FGuid, Vec<PartLocation<'a>>>,
+    compress_chunks: bool,
+}
+
+impl<'a> ChunkHarvester<'a> {
+    pub fn new(manifest: &'a FManifest, install_dir: impl AsRef<Path>) -> ChunkHarvester<'a> {
+        ChunkHarvester {
+            manifest,
+            install_dir: install_dir.as_ref().to_path_buf(),
+            parts: index_chunk_parts(manifest),
+            compress_chunks: true,
+        }
+    }
+
+    /// This function is used to choose if the chunk files written to the store are compressed, they are by default
+    pub fn with_compressed_chunks(mut self, compress_chunks: bool) -> ChunkHarvester<'a> {
+        self.compress_chunks = compress_chunks;
+        self
+    }
+
+    /// Chunks of the manifest that have at least one piece in the installed files
+    pub fn harvestable_chunks(&self) -> Vec<&'a FChunkInfo> {
+        let manifest = self.manifest;
+        manifest.chunk_list.chunks().iter().filter(|chunk| self.parts.contains_key(chunk.guid())).collect()
+    }
+
+    /// This function is used to rebuild a single chunk from the installed files and to verify it against its SHA1 and rolling hash
+    pub fn harvest_chunk(&self, chunk: &FChunkInfo) -> ParseResult<FChunk> {
+        let parts = self.parts.get(chunk.guid()).ok_or(ParseError::MissingChunk(*chunk.guid()))?;
+        let mut data = vec![0u8; chunk.uncompressed_size() as usize];
+
+        let mut input: Option<(&str, File)> = None;
+        for part in parts {
+            let start = part.offset as usize;
+            let end = start + part.size as usize;
+            let buffer = data.get_mut(start..end).ok_or(ParseError::Overflow)?;
+
+            if !matches!(&input, Some((filename, _)) if *filename == part.filename) {
+                input = Some((part.filename, File::open(install_path(&self.install_dir, part.filename)?)?));
+            }
+
+            let (_, file) = input.as_mut().ok_or(ParseError::InvalidData)?;
+            file.seek(SeekFrom::Start(part.file_offset as u64))?;
+            file.read_exact(buffer)?;
+        }
+
+        if !is_chunk_data_valid(chunk, &data) {
+            return Err(ParseError::HashMismatch);
+        }
+
+        Ok(FChunk::new(*chunk.guid(), data))
+    }
+
+    /// This function is used to harvest every chunk of the manifest into `store`, see `harvest_chunks`
+    pub fn harvest(&self, store: &mut ChunkStore) -> ParseResult<Vec<FGuid>> {
+        self.harvest_chunks(&self.harvestable_chunks(), store)
+    }
+
+    /// This function is used to harvest some chunks and write them into the directory of `store`, using the CDN layout of the manifest.
+    /// Chunks already in the store are skipped, as well as chunks whose files are missing or too short and chunks that don't match their hashes.
+    /// The GUIDs of the chunks added to the store are returned.
+    pub fn harvest_chunks(&self, chunks: &[&FChunkInfo], store: &mut ChunkStore) -> ParseResult<Vec<FGuid>> {
+        let feature_level = self.manifest.meta.feature_level();
+        let mut harvested = vec![];
+
+        for chunk in chunks {
+            if store.contains(chunk.guid()) {
+                continue;
+            }
+
+            let chunk_file = match self.harvest_chunk(chunk) {
+                Ok(harvested_chunk) => harvested_chunk.to_bytes(self.compress_chunks)?,
+                // the install doesn't hold this chunk anymore, other errors are not expected
+                Err(ParseError::HashMismatch | ParseError::MissingChunk(_)) => continue,
+                Err(ParseError::IoError(ErrorKind::NotFound | ErrorKind::UnexpectedEof)) => continue,
+                Err(error) => return Err(error),
+            };
+
+            let path = store.root().join(chunk_path(chunk, feature_level));
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, chunk_file)?;
+
+            store.insert(*chunk.guid(), path);
+            harvested.push(*chunk.guid());
+        }
+
+        Ok(harvested)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{install::chunk_source::ChunkSource, manifest::builder::{EChunkingMode, ManifestBuilder}, test_utils::{random_data, TempDir}};
+
+    use super::*;
+
+    fn build(install_dir: &TempDir, cloud_dir: &TempDir) -> FManifest {
+        install_dir.write("a.bin", &random_data(100 * 1024, 18));
+        install_dir.write("data/b.bin", &random_data(200 * 1024, 19));
+
+        ManifestBuilder::new("Test", "1").with_chunking(EChunkingMode::Fixed, 64 * 1024).build(install_dir.path(), cloud_dir.path()).unwrap()
+    }
+
+    #[test]
+    fn chunks_are_harvested_from_an_install() {
+        let install_dir = TempDir::new("harvest_install");
+        let cloud_dir = TempDir::new("harvest_cloud");
+        let store_dir = TempDir::new("harvest_store");
+        let manifest = build(&install_dir, &cloud_dir);
+
+        let mut store = ChunkStore::open(store_dir.path()).unwrap();
+        let harvested = ChunkHarvester::new(&manifest, install_dir.path()).harvest(&mut store).unwrap();
+        assert_eq!(harvested.len(), manifest.chunk_list.chunks().len());
+
+        // the harvested chunks are found again once the store is reopened, at their CDN path
+        let store = ChunkStore::open(store_dir.path()).unwrap();
+        for chunk in manifest.chunk_list.chunks() {
+            assert_eq!(store.path(chunk.guid()).unwrap(), store_dir.join(manifest.chunk_path(chunk)));
+            store.fetch_chunk(chunk).unwrap();
+        }
+    }
+
+    #[test]
+    fn chunks_of_modified_files_are_not_harvested() {
+        let install_dir = TempDir::new("harvest_modified_install");
+        let cloud_dir = TempDir::new("harvest_modified_cloud");
+        let store_dir = TempDir::new("harvest_modified_store");
+        let manifest = build(&install_dir, &cloud_dir);
+
+        let mut data = fs::read(install_dir.join("data/b.bin")).unwrap();
+        *data.last_mut().unwrap() ^= 0xFF;
+        fs::write(install_dir.join("data/b.bin"), data).unwrap();
+
+        let file = manifest.file_list.entries().iter().find(|file| file.filename() == "data/b.bin").unwrap();
+        let modified_chunk = *file.chunk_parts().last().unwrap().guid();
+
+        let harvester = ChunkHarvester::new(&manifest, install_dir.path());
+        assert!(matches!(harvester.harvest_chunk(manifest.chunk_list.chunks().iter().find(|chunk| *chunk.guid() == modified_chunk).unwrap()), Err(ParseError::HashMismatch)));
+
+        let mut store = ChunkStore::open(store_dir.path()).unwrap();
+        let harvested = harvester.harvest(&mut store).unwrap();
+        assert_eq!(harvested.len(), manifest.chunk_list.chunks().len() - 1);
+        assert!(!harvested.contains(&modified_chunk));
+        assert!(!store.contains(&modified_chunk));
+    }
+}
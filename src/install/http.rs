@@ -0,0 +1,329 @@
+use std::{io::Read, sync::{atomic::{AtomicUsize, Ordering}, Arc}, thread, time::Duration};
+
+use crate::{crypto::Decryptor, error::ParseError, manifest::{chunk_info::{chunk_url, FChunkInfo}, chunks::chunk::FChunk, shared::EFeatureLevel, FManifest}, reader::ByteReader, ParseResult};
+
+use super::chunk_source::{verify_chunk, ChunkSource};
+
+/// Default amount of retries of a download on the same mirror, before moving to the next mirror
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+/// Default delay before the first retry, it is doubled on every following retry
+pub const DEFAULT_RETRY_DELAY: Duration = Duration::from_millis(500);
+/// Default timeout of a single request
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+/// Default size limit of a downloaded chunk file, Epic's chunks hold at most 1 MiB of data
+pub const DEFAULT_MAX_DOWNLOAD_SIZE: u64 = 16 * 1024 * 1024;
+
+/// This type downloads chunks from the CDN, it is available with the `http` feature.
+/// Base URLs are the CloudDir of the build on every mirror, chunks are requested with the layout of the feature level of the manifest.
+/// Mirrors are tried in order, starting from the last one that worked: a failed download is retried with an exponential backoff, then the next mirror is used.
+/// A downloaded chunk is only accepted once its hashes are verified, a corrupt download is retried like a failed one.
+pub struct HttpChunkSource {
+    agent: ureq::Agent,
+    base_urls: Vec<String>,
+    current_mirror: AtomicUsize,
+    feature_level: EFeatureLevel,
+    max_retries: u32,
+    retry_delay: Duration,
+    max_download_size: u64,
+    decryptor: Option<Arc<dyn Decryptor>>,
+}
+
+impl std::fmt::Debug for HttpChunkSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HttpChunkSource")
+            .field("base_urls", &self.base_urls)
+            .field("feature_level", &self.feature_level)
+            .field("max_retries", &self.max_retries)
+            .field("retry_delay", &self.retry_delay)
+            .field("max_download_size", &self.max_download_size)
+            .field("decryptor", &self.decryptor.is_some())
+            .finish()
+    }
+}
+
+impl HttpChunkSource {
+    pub fn new<S: Into<String>>(base_urls: impl IntoIterator<Item = S>, feature_level: EFeatureLevel) -> HttpChunkSource {
+        HttpChunkSource {
+            agent: ureq::AgentBuilder::new().timeout(DEFAULT_TIMEOUT).build(),
+            base_urls: base_urls.into_iter().map(|base_url| base_url.into()).collect(),
+            current_mirror: AtomicUsize::new(0),
+            feature_level,
+            max_retries: DEFAULT_MAX_RETRIES,
+            retry_delay: DEFAULT_RETRY_DELAY,
+            max_download_size: DEFAULT_MAX_DOWNLOAD_SIZE,
+            decryptor: None,
+        }
+    }
+
+    /// This function is used to download the chunks of `manifest` from the given mirrors
+    pub fn for_manifest<S: Into<String>>(manifest: &FManifest, base_urls: impl IntoIterator<Item = S>) -> HttpChunkSource {
+        HttpChunkSource::new(base_urls, manifest.meta.feature_level())
+    }
+
+    pub fn with_max_retries(mut self, max_retries: u32) -> HttpChunkSource {
+        self.max_retries = max_retries;
+        self
+    }
+
+    pub fn with_retry_delay(mut self, retry_delay: Duration) -> HttpChunkSource {
+        self.retry_delay = retry_delay;
+        self
+    }
+
+    /// This function is used to set the size limit of a downloaded chunk file, a larger response is treated as a failed download
+    pub fn with_max_download_size(mut self, max_download_size: u64) -> HttpChunkSource {
+        self.max_download_size = max_download_size;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> HttpChunkSource {
+        self.agent = ureq::AgentBuilder::new().timeout(timeout).build();
+        self
+    }
+
+    /// This function is used to set the decryptor used to read encrypted chunks
+    pub fn set_decryptor(&mut self, decryptor: impl Decryptor + 'static) {
+        self.decryptor = Some(Arc::new(decryptor));
+    }
+
+    pub fn base_urls(&self) -> &[String] {
+        &self.base_urls
+    }
+
+    /// This function is used to download a chunk file from a single URL, without any retry.
+    /// Responses larger than the size limit are rejected without being read entirely.
+    pub fn download(&self, url: &str) -> ParseResult<Vec<u8>> {
+        let response = self.agent.get(url).call().map_err(|error| ParseError::DownloadError {
+            url: url.to_owned(),
+            status: match error {
+                ureq::Error::Status(status, _) => Some(status),
+                ureq::Error::Transport(_) => None,
+            },
+        })?;
+
+        // one more byte than the limit is read to know if the response is larger
+        let mut data = vec![];
+        response.into_reader().take(self.max_download_size + 1).read_to_end(&mut data)?;
+
+        if data.len() as u64 > self.max_download_size {
+            return Err(ParseError::DownloadError {
+                url: url.to_owned(),
+                status: None,
+            });
+        }
+
+        Ok(data)
+    }
+
+    fn download_chunk(&self, url: &str, chunk: &FChunkInfo) -> ParseResult<FChunk> {
+        let data = self.download(url)?;
+        let downloaded_chunk = FChunk::parse_with_decryptor(&mut ByteReader::new(data), self.decryptor.as_deref())?;
+        verify_chunk(chunk, &downloaded_chunk)?;
+
+        Ok(downloaded_chunk)
+    }
+}
+
+impl ChunkSource for HttpChunkSource {
+    fn fetch_chunk(&self, chunk: &FChunkInfo) -> ParseResult<FChunk> {
+        let mut last_error = ParseError::MissingChunk(*chunk.guid());
+
+        let first_mirror = self.current_mirror.load(Ordering::Relaxed);
+        for mirror in (0..self.base_urls.len()).map(|index| (first_mirror + index) % self.base_urls.len()) {
+            let url = chunk_url(&self.base_urls[mirror], chunk, self.feature_level);
+
+            for attempt in 0..=self.max_retries {
+                if attempt > 0 {
+                    thread::sleep(self.retry_delay.saturating_mul(1 << (attempt - 1).min(16)));
+                }
+
+                match self.download_chunk(&url, chunk) {
+                    Ok(downloaded_chunk) => {
+                        self.current_mirror.store(mirror, Ordering::Relaxed);
+                        return Ok(downloaded_chunk);
+                    },
+                    // the mirror doesn't have the chunk, retrying won't help
+                    Err(error @ ParseError::DownloadError { status: Some(status), .. }) if is_permanent_error(status) => {
+                        last_error = error;
+                        break;
+                    },
+                    Err(error) => last_error = error,
+                }
+            }
+        }
+
+        Err(last_error)
+    }
+
+    fn has_chunk(&self, _chunk: &FChunkInfo) -> bool {
+        !self.base_urls.is_empty()
+    }
+}
+
+// client errors are permanent, except timeouts and rate limiting
+fn is_permanent_error(status: u16) -> bool {
+    (400..500).contains(&status) && status != 408 && status != 429
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::VecDeque, io::Write, net::TcpListener, sync::Mutex};
+
+    use crate::{manifest::{chunk_info::chunk_path, shared::{FGuid, FSHAHash}}, rolling_hash::FRollingHash, test_utils::random_data};
+
+    use super::*;
+
+    // a mirror answering every request with the next scripted response, 500 once the script is over
+    struct TestMirror {
+        url: String,
+        requests: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl TestMirror {
+        fn start(responses: Vec<(u16, Vec<u8>)>) -> TestMirror {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}/CloudDir", listener.local_addr().unwrap());
+            let requests = Arc::new(Mutex::new(vec![]));
+            let mut responses = VecDeque::from(responses);
+
+            let server_requests = requests.clone();
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let mut stream = stream.unwrap();
+
+                    let mut request = vec![];
+                    let mut byte = [0u8];
+                    while !request.ends_with(b"\r\n\r\n") && stream.read(&mut byte).unwrap_or(0) == 1 {
+                        request.push(byte[0]);
+                    }
+
+                    let request = String::from_utf8_lossy(&request).into_owned();
+                    server_requests.lock().unwrap().push(request.split(' ').nth(1).unwrap_or_default().to_owned());
+
+                    let (status, body) = responses.pop_front().unwrap_or((500, vec![]));
+                    let _ = write!(stream, "HTTP/1.1 {} Test\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", status, body.len());
+                    let _ = stream.write_all(&body);
+                }
+            });
+
+            TestMirror {
+                url,
+                requests,
+            }
+        }
+
+        fn requests(&self) -> Vec<String> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    fn chunk_info(data: &[u8]) -> FChunkInfo {
+        FChunkInfo {
+            guid: FGuid { a: 1, b: 2, c: 3, d: 4 },
+            hash: FRollingHash::get_hash_for_data_set(data),
+            sha_hash: FSHAHash::new_from_hashable(data),
+            uncompressed_size: data.len() as u32,
+            ..Default::default()
+        }
+    }
+
+    fn chunk_file(data: &[u8]) -> (u16, Vec<u8>) {
+        (200, FChunk::new(FGuid { a: 1, b: 2, c: 3, d: 4 }, data.to_vec()).to_bytes(true).unwrap())
+    }
+
+    fn source(mirrors: &[&TestMirror]) -> HttpChunkSource {
+        HttpChunkSource::new(mirrors.iter().map(|mirror| mirror.url.clone()), EFeatureLevel::Latest)
+            .with_max_retries(2)
+            .with_retry_delay(Duration::from_millis(1))
+    }
+
+    #[test]
+    fn next_mirror_is_used_once_retries_are_exhausted() {
+        let data = random_data(1000, 12);
+        let chunk = chunk_info(&data);
+
+        let broken_mirror = TestMirror::start(vec![]);
+        let mirror = TestMirror::start(vec![chunk_file(&data), chunk_file(&data)]);
+        let source = source(&[&broken_mirror, &mirror]);
+
+        assert_eq!(source.fetch_chunk(&chunk).unwrap().data(), data);
+        assert_eq!(broken_mirror.requests().len(), 3);
+        assert_eq!(mirror.requests(), [format!("/CloudDir/{}", chunk_path(&chunk, EFeatureLevel::Latest))]);
+
+        // the mirror that worked is tried first for the next chunks
+        assert_eq!(source.fetch_chunk(&chunk).unwrap().data(), data);
+        assert_eq!(broken_mirror.requests().len(), 3);
+        assert_eq!(mirror.requests().len(), 2);
+    }
+
+    #[test]
+    fn server_errors_are_retried() {
+        let data = random_data(1000, 13);
+        let mirror = TestMirror::start(vec![(503, vec![]), (500, vec![]), chunk_file(&data)]);
+
+        let source = source(&[&mirror]).with_retry_delay(Duration::from_millis(20));
+
+        // the delay doubles on every retry
+        let start = std::time::Instant::now();
+        assert_eq!(source.fetch_chunk(&chunk_info(&data)).unwrap().data(), data);
+        assert!(start.elapsed() >= Duration::from_millis(20 + 40));
+        assert_eq!(mirror.requests().len(), 3);
+    }
+
+    #[test]
+    fn corrupt_downloads_are_retried() {
+        let data = random_data(1000, 14);
+        let (_, mut corrupt_file) = chunk_file(&data);
+        corrupt_file.truncate(corrupt_file.len() / 2);
+
+        let mirror = TestMirror::start(vec![(200, corrupt_file), chunk_file(&data)]);
+
+        assert_eq!(source(&[&mirror]).fetch_chunk(&chunk_info(&data)).unwrap().data(), data);
+        assert_eq!(mirror.requests().len(), 2);
+    }
+
+    #[test]
+    fn chunks_not_matching_their_hashes_are_rejected() {
+        let data = random_data(1000, 15);
+        let other_data = random_data(1000, 16);
+        let mirror = TestMirror::start(vec![chunk_file(&other_data); 3]);
+
+        assert!(matches!(source(&[&mirror]).fetch_chunk(&chunk_info(&data)), Err(ParseError::HashMismatch)));
+        assert_eq!(mirror.requests().len(), 3);
+    }
+
+    #[test]
+    fn missing_chunks_are_not_retried() {
+        let data = random_data(1000, 17);
+        let chunk = chunk_info(&data);
+
+        let incomplete_mirror = TestMirror::start(vec![(404, vec![])]);
+        let mirror = TestMirror::start(vec![chunk_file(&data)]);
+
+        assert_eq!(source(&[&incomplete_mirror, &mirror]).fetch_chunk(&chunk).unwrap().data(), data);
+        assert_eq!(incomplete_mirror.requests().len(), 1);
+
+        let incomplete_mirror = TestMirror::start(vec![(404, vec![])]);
+        assert!(matches!(source(&[&incomplete_mirror]).fetch_chunk(&chunk), Err(ParseError::DownloadError { status: Some(404), .. })));
+        assert_eq!(incomplete_mirror.requests().len(), 1);
+    }
+
+    #[test]
+    fn oversized_downloads_are_rejected() {
+        let data = random_data(1000, 18);
+        let (status, chunk_file) = chunk_file(&data);
+        let oversized_file = [chunk_file.clone(), vec![0; 1000]].concat();
+
+        let mirror = TestMirror::start(vec![(status, oversized_file.clone()), (status, chunk_file.clone())]);
+        let limited_source = source(&[&mirror]).with_max_download_size(chunk_file.len() as u64);
+
+        assert!(matches!(limited_source.download(&mirror.url), Err(ParseError::DownloadError { status: None, .. })));
+        assert_eq!(limited_source.download(&mirror.url).unwrap(), chunk_file);
+
+        // an oversized response is retried like a failed download
+        let mirror = TestMirror::start(vec![(status, oversized_file); 3]);
+        let limited_source = source(&[&mirror]).with_max_download_size(chunk_file.len() as u64);
+        assert!(matches!(limited_source.fetch_chunk(&chunk_info(&data)), Err(ParseError::DownloadError { status: None, .. })));
+        assert_eq!(mirror.requests().len(), 3);
+    }
+}
@@ -0,0 +1,181 @@
+// Everything needed to turn a manifest and its chunks back into installed files.
+
+use std::{fs, io::ErrorKind, path::{Path, PathBuf}};
+
+use crate::{error::ParseError, manifest::shared::EFileMetaFlags, ParseResult};
+
+pub mod chunk_source;
+pub mod chunk_store;
+pub mod harvest;
+pub mod patch;
+pub mod reconstruct;
+pub mod resume;
+pub mod verify;
+#[cfg(feature = "rayon")]
+pub mod parallel;
+#[cfg(feature = "http")]
+pub mod http;
+
+/// This function is used to get the path a file of the manifest is installed to.
+/// Manifests use `/` as separator, absolute paths and `..` components are rejected so a manifest can't write outside of `root`.
+pub fn install_path(root: &Path, filename: &str) -> ParseResult<PathBuf> {
+    let mut path = root.to_path_buf();
+
+    for component in filename.split(['/', '\\']) {
+        match component {
+            "" | "." => continue,
+            ".." => return Err(ParseError::InvalidPath(filename.to_owned())),
+            component if component.contains(':') => return Err(ParseError::InvalidPath(filename.to_owned())),
+            component => path.push(component),
+        }
+    }
+
+    if path == root {
+        return Err(ParseError::InvalidPath(filename.to_owned()));
+    }
+
+    Ok(path)
+}
+
+/// This function is used to resolve the target of a symlink of the manifest, relative to the directory of the link at `link_path`.
+/// Absolute targets and targets pointing outside of `root` are rejected, symlinks already installed in the parent directories are followed.
+/// The parent directory of the link must exist.
+pub fn symlink_target_path(root: &Path, link_path: &Path, target: &str) -> ParseResult<PathBuf> {
+    let invalid_target = || ParseError::InvalidPath(target.to_owned());
+
+    let root = root.canonicalize()?;
+    let mut path = link_path.parent().ok_or_else(invalid_target)?.canonicalize()?;
+
+    if !path.starts_with(&root) || target.starts_with(['/', '\\']) {
+        return Err(invalid_target());
+    }
+
+    for component in target.split(['/', '\\']) {
+        match component {
+            "" | "." => continue,
+            ".." if path == root => return Err(invalid_target()),
+            ".." => { path.pop(); },
+            component if component.contains(':') => return Err(invalid_target()),
+            component => path.push(component),
+        }
+    }
+
+    // an existing target may itself go through other symlinks
+    if let Ok(canonical_path) = path.canonicalize() {
+        if !canonical_path.starts_with(&root) {
+            return Err(invalid_target());
+        }
+    }
+
+    Ok(path)
+}
+
+/// This function is used to install a symlink of the manifest at `path`, replacing the file or symlink already there
+pub(crate) fn create_symlink(root: &Path, path: &Path, target: &str) -> ParseResult<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let target_path = symlink_target_path(root, path, target)?;
+
+    remove_symlink(path)?;
+    if path.is_file() {
+        make_writable(path)?;
+        fs::remove_file(path)?;
+    }
+
+    symlink(target, &target_path, path)?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn symlink(target: &str, _target_path: &Path, path: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target.replace('\\', "/"), path)
+}
+
+// Windows needs to know if the target is a directory
+#[cfg(windows)]
+fn symlink(target: &str, target_path: &Path, path: &Path) -> std::io::Result<()> {
+    let target = target.replace('/', "\\");
+
+    if target_path.is_dir() {
+        std::os::windows::fs::symlink_dir(target, path)
+    } else {
+        std::os::windows::fs::symlink_file(target, path)
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn symlink(_target: &str, _target_path: &Path, _path: &Path) -> std::io::Result<()> {
+    Err(ErrorKind::Unsupported.into())
+}
+
+/// This function is used to remove a symlink found where a regular file is about to be written, so the file isn't written through it
+pub(crate) fn remove_symlink(path: &Path) -> ParseResult<()> {
+    match fs::symlink_metadata(path) {
+        // symlinks to directories are removed as directories on Windows
+        Ok(metadata) if metadata.file_type().is_symlink() => fs::remove_file(path).or_else(|_| fs::remove_dir(path))?,
+        Ok(_) => {},
+        Err(error) if error.kind() == ErrorKind::NotFound => {},
+        Err(error) => return Err(error.into()),
+    }
+
+    Ok(())
+}
+
+/// This function is used to apply the attributes of a file of the manifest to an installed file.
+/// `UnixExecutable` adds the executable permission wherever the file is readable and its absence removes every executable permission (only on Unix).
+/// `ReadOnly` removes every write permission and its absence lets the owner write the file again.
+pub fn apply_file_flags(path: &Path, flags: EFileMetaFlags) -> ParseResult<()> {
+    let mut permissions = fs::metadata(path)?.permissions();
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut mode = permissions.mode();
+        if flags.contains(EFileMetaFlags::UnixExecutable) {
+            mode |= (mode & 0o444) >> 2;
+        } else {
+            mode &= !0o111;
+        }
+
+        if flags.contains(EFileMetaFlags::ReadOnly) {
+            mode &= !0o222;
+        } else if mode & 0o222 == 0 {
+            mode |= 0o200;
+        }
+
+        permissions.set_mode(mode);
+    }
+
+    #[cfg(not(unix))]
+    #[allow(clippy::permissions_set_readonly_false)]
+    permissions.set_readonly(flags.contains(EFileMetaFlags::ReadOnly));
+
+    fs::set_permissions(path, permissions)?;
+    Ok(())
+}
+
+/// This function is used to let the owner write an installed file again, files installed read-only can't be repaired or patched otherwise
+pub(crate) fn make_writable(path: &Path) -> ParseResult<()> {
+    let mut permissions = match fs::metadata(path) {
+        Ok(metadata) if metadata.permissions().readonly() => metadata.permissions(),
+        Ok(_) => return Ok(()),
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(error) => return Err(error.into()),
+    };
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        permissions.set_mode(permissions.mode() | 0o200);
+    }
+
+    #[cfg(not(unix))]
+    #[allow(clippy::permissions_set_readonly_false)]
+    permissions.set_readonly(false);
+
+    fs::set_permissions(path, permissions)?;
+    Ok(())
+}
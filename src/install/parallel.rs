@@ -0,0 +1,316 @@
+use std::{collections::{HashMap, HashSet}, fs::{self, File, OpenOptions}, io::{BufWriter, Seek, SeekFrom, Write}, path::Path};
+
+use rayon::prelude::*;
+use sha1::{Digest, Sha1};
+
+use crate::{error::ParseError, manifest::{chunk_info::FChunkInfo, chunk_part::FChunkPart, chunks::chunk::FChunk, file_manifest::FFileManifest, shared::FGuid, FManifest}, ParseResult};
+
+use super::{apply_file_flags, chunk_source::ChunkSource, chunk_store::ChunkStore, create_symlink, install_path, make_writable, remove_symlink};
+
+/// Default amount of decompressed chunk data kept in memory at once
+pub const DEFAULT_MEMORY_BUDGET: usize = 512 * 1024 * 1024;
+
+/// This function is used to read, decompress and verify many chunks at once, using every thread of the rayon pool
+pub fn read_chunks(store: &ChunkStore, guids: &[FGuid]) -> ParseResult<Vec<FChunk>> {
+    guids.par_iter().map(|guid| store.read_chunk(guid)).collect()
+}
+
+// a run of chunk parts of a file, files are only split when their chunks don't fit in the memory budget
+struct Segment<'a> {
+    file: &'a FFileManifest,
+    parts: &'a [FChunkPart],
+    is_first: bool,
+    is_last: bool,
+}
+
+impl Segment<'_> {
+    fn is_whole_file(&self) -> bool {
+        self.is_first && self.is_last
+    }
+}
+
+#[derive(Default)]
+struct Batch<'a> {
+    segments: Vec<Segment<'a>>,
+    chunks: HashSet<FGuid>,
+    size: usize,
+}
+
+/// This type is the parallel counterpart of `FileReconstructor`, it is available with the `rayon` feature.
+/// Files are installed in batches: the chunks of a batch are decompressed and verified in parallel, then its files are written concurrently.
+/// A batch never holds more decompressed chunk data than the memory budget, files whose chunks don't fit are written in several batches.
+pub struct ParallelReconstructor<'a> {
+    manifest: &'a FManifest,
+    source: &'a dyn ChunkSource,
+    chunks: HashMap<FGuid, &'a FChunkInfo>,
+    memory_budget: usize,
+}
+
+impl<'a> ParallelReconstructor<'a> {
+    pub fn new(manifest: &'a FManifest, source: &'a dyn ChunkSource) -> ParallelReconstructor<'a> {
+        ParallelReconstructor {
+            manifest,
+            source,
+            chunks: manifest.chunk_list.chunks().iter().map(|chunk| (*chunk.guid(), chunk)).collect(),
+            memory_budget: DEFAULT_MEMORY_BUDGET,
+        }
+    }
+
+    /// This function is used to set the maximum amount of decompressed chunk data kept in memory, a single chunk is always allowed
+    pub fn with_memory_budget(mut self, memory_budget: usize) -> ParallelReconstructor<'a> {
+        self.memory_budget = memory_budget;
+        self
+    }
+
+    /// This function is used to rebuild every file of the manifest into `install_dir`
+    pub fn reconstruct_all(&self, install_dir: impl AsRef<Path>) -> ParseResult<()> {
+        let manifest = self.manifest;
+        self.reconstruct_files(&manifest.file_list.entries().iter().collect::<Vec<_>>(), install_dir)
+    }
+
+    /// This function is used to rebuild some files of the manifest into `install_dir`, every file is verified against `FFileManifest::hash`.
+    /// Symlinks are created once every regular file is written.
+    pub fn reconstruct_files(&self, files: &[&'a FFileManifest], install_dir: impl AsRef<Path>) -> ParseResult<()> {
+        let install_dir = install_dir.as_ref();
+        let (symlinks, files): (Vec<&'a FFileManifest>, Vec<&'a FFileManifest>) = files.iter().copied().partition(|file| file.is_symlink());
+        let mut split_hasher = Sha1::new();
+
+        for batch in self.plan(&files) {
+            let chunks: HashMap<FGuid, Vec<u8>> = batch.chunks.par_iter()
+                .map(|guid| self.fetch_chunk(guid).map(|chunk| (*guid, chunk.into_data())))
+                .collect::<ParseResult<_>>()?;
+
+            match batch.segments.as_slice() {
+                // a part of a file bigger than the budget, parts are hashed in order across batches
+                [segment] if !segment.is_whole_file() => {
+                    if segment.is_first {
+                        split_hasher = Sha1::new();
+                    }
+
+                    write_segment(segment, &chunks, install_dir, &mut split_hasher)?;
+
+                    if segment.is_last {
+                        if split_hasher.finalize_reset().as_slice() != segment.file.hash().data() {
+                            return Err(ParseError::HashMismatch);
+                        }
+
+                        apply_file_flags(&install_path(install_dir, segment.file.filename())?, segment.file.flags())?;
+                    }
+                },
+                segments => {
+                    segments.par_iter().try_for_each(|segment| {
+                        let mut hasher = Sha1::new();
+                        write_segment(segment, &chunks, install_dir, &mut hasher)?;
+
+                        if hasher.finalize().as_slice() != segment.file.hash().data() {
+                            return Err(ParseError::HashMismatch);
+                        }
+
+                        apply_file_flags(&install_path(install_dir, segment.file.filename())?, segment.file.flags())
+                    })?;
+                }
+            }
+        }
+
+        for symlink in symlinks {
+            create_symlink(install_dir, &install_path(install_dir, symlink.filename())?, symlink.syslink_target())?;
+        }
+
+        Ok(())
+    }
+
+    fn fetch_chunk(&self, guid: &FGuid) -> ParseResult<FChunk> {
+        let chunk_info = self.chunks.get(guid).ok_or(ParseError::MissingChunk(*guid))?;
+        self.source.fetch_chunk(chunk_info)
+    }
+
+    fn chunk_size(&self, guid: &FGuid) -> usize {
+        self.chunks.get(guid).map(|chunk| chunk.uncompressed_size() as usize).unwrap_or_default()
+    }
+
+    fn plan(&self, files: &[&'a FFileManifest]) -> Vec<Batch<'a>> {
+        let mut batches = vec![];
+        let mut batch = Batch::default();
+
+        for file in files {
+            let file_chunks: HashSet<FGuid> = file.chunk_parts().iter().map(|part| *part.guid()).collect();
+            let file_size: usize = file_chunks.iter().map(|guid| self.chunk_size(guid)).sum();
+
+            if file_size <= self.memory_budget {
+                let mut new_size: usize = file_chunks.iter().filter(|guid| !batch.chunks.contains(guid)).map(|guid| self.chunk_size(guid)).sum();
+
+                if batch.size + new_size > self.memory_budget {
+                    batches.push(std::mem::take(&mut batch));
+                    new_size = file_size;
+                }
+
+                batch.size += new_size;
+                batch.chunks.extend(file_chunks);
+                batch.segments.push(Segment {
+                    file,
+                    parts: file.chunk_parts(),
+                    is_first: true,
+                    is_last: true,
+                });
+                continue;
+            }
+
+            if !batch.segments.is_empty() {
+                batches.push(std::mem::take(&mut batch));
+            }
+
+            let parts = file.chunk_parts();
+            let mut start = 0;
+            while start < parts.len() {
+                let mut chunks = HashSet::new();
+                let mut size = 0;
+                let mut end = start;
+
+                while end < parts.len() {
+                    let guid = *parts[end].guid();
+                    if !chunks.contains(&guid) {
+                        let chunk_size = self.chunk_size(&guid);
+                        if end > start && size + chunk_size > self.memory_budget {
+                            break;
+                        }
+
+                        size += chunk_size;
+                        chunks.insert(guid);
+                    }
+                    end += 1;
+                }
+
+                batches.push(Batch {
+                    segments: vec![Segment {
+                        file,
+                        parts: &parts[start..end],
+                        is_first: start == 0,
+                        is_last: end == parts.len(),
+                    }],
+                    chunks,
+                    size,
+                });
+                start = end;
+            }
+        }
+
+        if !batch.segments.is_empty() {
+            batches.push(batch);
+        }
+
+        batches
+    }
+}
+
+fn write_segment(segment: &Segment, chunks: &HashMap<FGuid, Vec<u8>>, install_dir: &Path, hasher: &mut Sha1) -> ParseResult<()> {
+    let path = install_path(install_dir, segment.file.filename())?;
+
+    let mut output = if segment.is_first {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        remove_symlink(&path)?;
+        make_writable(&path)?;
+        File::create(&path)?
+    } else {
+        let mut output = OpenOptions::new().write(true).open(&path)?;
+        output.seek(SeekFrom::Start(segment.parts.first().map(|part| part.file_offset() as u64).unwrap_or_default()))?;
+        output
+    };
+
+    let mut output_writer = BufWriter::new(&mut output);
+    for part in segment.parts {
+        let chunk_data = chunks.get(part.guid()).ok_or(ParseError::MissingChunk(*part.guid()))?;
+
+        let start = part.offset() as usize;
+        let end = start + part.size() as usize;
+        let part_data = chunk_data.get(start..end).ok_or(ParseError::Overflow)?;
+
+        hasher.update(part_data);
+        output_writer.write_all(part_data)?;
+    }
+    output_writer.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{manifest::builder::{EChunkingMode, ManifestBuilder}, test_utils::{random_data, TempDir}};
+
+    use super::*;
+
+    const MEMORY_BUDGET: usize = 2 * 64 * 1024;
+
+    fn build(source: &TempDir, cloud_dir: &TempDir) -> FManifest {
+        source.write("big.bin", &random_data(300 * 1024, 21));
+        for (index, name) in ["small/a.bin", "small/b.bin", "small/c.bin"].iter().enumerate() {
+            source.write(name, &random_data(10 * 1024, 22 + index as u64));
+        }
+
+        ManifestBuilder::new("Test", "1").with_chunking(EChunkingMode::Fixed, 64 * 1024).build(source.path(), cloud_dir.path()).unwrap()
+    }
+
+    #[test]
+    fn files_larger_than_the_budget_are_split() {
+        let source = TempDir::new("parallel_plan_source");
+        let cloud_dir = TempDir::new("parallel_plan_cloud");
+        let manifest = build(&source, &cloud_dir);
+        let store = ChunkStore::open(cloud_dir.path()).unwrap();
+
+        let reconstructor = ParallelReconstructor::new(&manifest, &store).with_memory_budget(MEMORY_BUDGET);
+        let batches = reconstructor.plan(&manifest.file_list.entries().iter().collect::<Vec<_>>());
+
+        let big_segments: Vec<&Segment> = batches.iter().flat_map(|batch| batch.segments.iter()).filter(|segment| segment.file.filename() == "big.bin").collect();
+        assert!(big_segments.len() >= 3);
+        assert!(big_segments[0].is_first && big_segments.last().unwrap().is_last);
+        assert_eq!(big_segments.iter().map(|segment| segment.parts.len()).sum::<usize>(), manifest.file_list.entries().iter().find(|file| file.filename() == "big.bin").unwrap().chunk_parts().len());
+
+        for batch in batches.iter() {
+            assert!(batch.size <= MEMORY_BUDGET);
+            assert!(batch.segments.len() == 1 || batch.segments.iter().all(|segment| segment.is_whole_file()));
+        }
+    }
+
+    #[test]
+    fn split_files_are_reconstructed() {
+        let source = TempDir::new("parallel_source");
+        let cloud_dir = TempDir::new("parallel_cloud");
+        let install_dir = TempDir::new("parallel_install");
+        let manifest = build(&source, &cloud_dir);
+        let store = ChunkStore::open(cloud_dir.path()).unwrap();
+
+        // the small files share chunks, they are written by the same batch
+        let small_chunks: HashSet<&FGuid> = manifest.file_list.entries().iter()
+            .filter(|file| file.filename().starts_with("small/"))
+            .flat_map(|file| file.chunk_parts())
+            .map(|part| part.guid())
+            .collect();
+        assert!(small_chunks.len() < 3);
+
+        ParallelReconstructor::new(&manifest, &store).with_memory_budget(MEMORY_BUDGET).reconstruct_all(install_dir.path()).unwrap();
+
+        for file in manifest.file_list.entries() {
+            assert_eq!(fs::read(install_dir.join(file.filename())).unwrap(), fs::read(source.join(file.filename())).unwrap(), "{}", file.filename());
+        }
+    }
+
+    #[test]
+    fn corrupted_parts_of_split_files_are_rejected() {
+        let source = TempDir::new("parallel_corrupt_source");
+        let cloud_dir = TempDir::new("parallel_corrupt_cloud");
+        let install_dir = TempDir::new("parallel_corrupt_install");
+        let mut manifest = build(&source, &cloud_dir);
+        let store = ChunkStore::open(cloud_dir.path()).unwrap();
+
+        // two parts of the same size swapped in the middle of the file: every chunk is valid but the file isn't
+        let big_file = manifest.file_list.entries.iter_mut().find(|file| file.filename() == "big.bin").unwrap();
+        let (first, second) = (big_file.chunk_parts[1].guid, big_file.chunk_parts[2].guid);
+        big_file.chunk_parts[1].guid = second;
+        big_file.chunk_parts[2].guid = first;
+
+        let reconstructor = ParallelReconstructor::new(&manifest, &store).with_memory_budget(MEMORY_BUDGET);
+        assert!(matches!(reconstructor.reconstruct_all(install_dir.path()), Err(ParseError::HashMismatch)));
+    }
+}
@@ -0,0 +1,359 @@
+use std::{collections::{HashMap, HashSet}, fs::{self, File}, io::{BufWriter, ErrorKind, Read, Seek, SeekFrom, Write}, path::{Path, PathBuf}};
+
+use sha1::{Digest, Sha1};
+
+use crate::{error::ParseError, manifest::{chunk_info::FChunkInfo, chunk_part::FChunkPart, diff::ManifestDiff, file_manifest::FFileManifest, shared::{FGuid, FSHAHash}, FManifest}, ParseResult};
+
+use super::{apply_file_flags, chunk_source::ChunkSource, create_symlink, install_path, make_writable, reconstruct::FileReconstructor, remove_symlink, resume::{staging_path, STAGING_DIRECTORY}};
+
+/// Where a piece of a chunk can be read in an install
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PartLocation<'a> {
+    pub(crate) filename: &'a str,
+    pub(crate) file_offset: usize,
+    pub(crate) offset: u32,
+    pub(crate) size: u32,
+}
+
+impl PartLocation<'_> {
+    fn contains(&self, part: &FChunkPart) -> bool {
+        self.offset <= part.offset() && part.offset() as u64 + part.size() as u64 <= self.offset as u64 + self.size as u64
+    }
+}
+
+/// This function is used to list where the pieces of every chunk are stored in the regular files of an install of `manifest`
+pub(crate) fn index_chunk_parts(manifest: &FManifest) -> HashMap<FGuid, Vec<PartLocation<'_>>> {
+    let mut parts: HashMap<FGuid, Vec<PartLocation>> = HashMap::new();
+
+    for file in manifest.file_list.entries().iter().filter(|file| !file.is_symlink()) {
+        for part in file.chunk_parts() {
+            parts.entry(*part.guid()).or_default().push(PartLocation {
+                filename: file.filename(),
+                file_offset: part.file_offset(),
+                offset: part.offset(),
+                size: part.size(),
+            });
+        }
+    }
+
+    parts
+}
+
+/// This type describes how to update an install from an old build to a new build.
+/// Files the ManifestDiff of the two builds reports as unchanged are kept, the chunk parts of the other files are read from the old files
+/// whenever the old manifest says they hold them, only the remaining chunks have to be downloaded.
+pub struct PatchPlan<'a> {
+    old_manifest: &'a FManifest,
+    new_manifest: &'a FManifest,
+    diff: ManifestDiff<'a>,
+    changed_files: Vec<&'a FFileManifest>,
+    required_chunks: Vec<&'a FChunkInfo>,
+    old_parts: HashMap<FGuid, Vec<PartLocation<'a>>>,
+}
+
+impl<'a> PatchPlan<'a> {
+    /// This function is used to compare two manifests, the old install is expected to match `old_manifest`
+    pub fn new(old_manifest: &'a FManifest, new_manifest: &'a FManifest) -> PatchPlan<'a> {
+        let diff = ManifestDiff::new(old_manifest, new_manifest);
+
+        let mut plan = PatchPlan {
+            old_manifest,
+            new_manifest,
+            changed_files: diff.added_files().iter().chain(diff.modified_files()).copied().collect(),
+            diff,
+            required_chunks: vec![],
+            old_parts: index_chunk_parts(old_manifest),
+        };
+
+        let required_guids: HashSet<&FGuid> = plan.changed_files.iter()
+            .flat_map(|file| file.chunk_parts())
+            .filter(|part| plan.find_old_part(part).is_none())
+            .map(|part| part.guid())
+            .collect();
+
+        plan.required_chunks = new_manifest.chunk_list.chunks().iter().filter(|chunk| required_guids.contains(chunk.guid())).collect();
+        plan
+    }
+
+    pub fn old_manifest(&self) -> &'a FManifest {
+        self.old_manifest
+    }
+
+    pub fn new_manifest(&self) -> &'a FManifest {
+        self.new_manifest
+    }
+
+    /// The comparison of the two builds the plan is made from
+    pub fn diff(&self) -> &ManifestDiff<'a> {
+        &self.diff
+    }
+
+    /// Files of the new build that are already installed
+    pub fn unchanged_files(&self) -> &[&'a FFileManifest] {
+        self.diff.unchanged_files()
+    }
+
+    /// Files of the new build that are added or modified
+    pub fn changed_files(&self) -> &[&'a FFileManifest] {
+        &self.changed_files
+    }
+
+    /// Files of the old build that are not part of the new build
+    pub fn removed_files(&self) -> &[&'a FFileManifest] {
+        self.diff.removed_files()
+    }
+
+    /// Chunks of the new build that can't be read from the old install, only these have to be downloaded
+    pub fn required_chunks(&self) -> &[&'a FChunkInfo] {
+        &self.required_chunks
+    }
+
+    /// Amount of bytes to download to apply the patch
+    pub fn download_size(&self) -> u64 {
+        self.required_chunks.iter().map(|chunk| chunk.compressed_size().max(0) as u64).sum()
+    }
+
+    /// Amount of bytes of the changed files read from the old install
+    pub fn reused_size(&self) -> u64 {
+        self.changed_files.iter()
+            .flat_map(|file| file.chunk_parts())
+            .filter(|part| self.find_old_part(part).is_some())
+            .map(|part| part.size() as u64)
+            .sum()
+    }
+
+    fn find_old_part(&self, part: &FChunkPart) -> Option<&PartLocation<'a>> {
+        self.old_parts.get(part.guid())?.iter().find(|location| location.contains(part))
+    }
+}
+
+/// This type is used to apply a PatchPlan to an install directory.
+/// Every changed file is written to a staging file first, the old files are only replaced once every staging file is verified
+/// so data can still be read from them. The chunks that are not in the old install are read from a ChunkSource.
+pub struct PatchInstaller<'a> {
+    plan: &'a PatchPlan<'a>,
+    reconstructor: FileReconstructor<'a>,
+    install_dir: PathBuf,
+    staging_dir: PathBuf,
+    old_file: Option<(&'a str, File)>,
+}
+
+impl<'a> PatchInstaller<'a> {
+    pub fn new(plan: &'a PatchPlan<'a>, source: &'a dyn ChunkSource, install_dir: impl AsRef<Path>) -> PatchInstaller<'a> {
+        let install_dir = install_dir.as_ref().to_path_buf();
+
+        PatchInstaller {
+            plan,
+            reconstructor: FileReconstructor::new(plan.new_manifest, source),
+            staging_dir: install_dir.join(STAGING_DIRECTORY),
+            install_dir,
+            old_file: None,
+        }
+    }
+
+    /// This function is used to write the staging files somewhere else than in the install directory, it must be on the same drive
+    pub fn with_staging_dir(mut self, staging_dir: impl AsRef<Path>) -> PatchInstaller<'a> {
+        self.staging_dir = staging_dir.as_ref().to_path_buf();
+        self
+    }
+
+    /// This function is used to update the install directory to the new build, files removed from the build are deleted once every changed file is staged.
+    /// A changed file that can't be rebuilt from the old install, because it doesn't match the old manifest, is rebuilt from the ChunkSource only.
+    pub fn apply(&mut self) -> ParseResult<()> {
+        let plan = self.plan;
+        fs::create_dir_all(&self.staging_dir)?;
+
+        let mut staged_files = Vec::with_capacity(plan.changed_files.len());
+        for file in plan.changed_files.iter().filter(|file| !file.is_symlink()) {
+            staged_files.push((*file, self.stage_file(file)?));
+        }
+        self.old_file = None;
+
+        for file in plan.removed_files().iter() {
+            self.remove_file(file)?;
+        }
+
+        for (file, staging_path) in staged_files {
+            let path = install_path(&self.install_dir, file.filename())?;
+
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            remove_symlink(&path)?;
+            make_writable(&path)?;
+            fs::rename(&staging_path, &path)?;
+            apply_file_flags(&path, file.flags())?;
+        }
+
+        for file in plan.changed_files.iter().filter(|file| file.is_symlink()) {
+            create_symlink(&self.install_dir, &install_path(&self.install_dir, file.filename())?, file.syslink_target())?;
+        }
+
+        for file in plan.unchanged_files().iter().filter(|file| !file.is_symlink()) {
+            apply_file_flags(&install_path(&self.install_dir, file.filename())?, file.flags())?;
+        }
+
+        // the staging directory is kept if it holds anything else
+        let _ = fs::remove_dir(&self.staging_dir);
+
+        Ok(())
+    }
+
+    fn stage_file(&mut self, file: &FFileManifest) -> ParseResult<PathBuf> {
+        let staging_path = staging_path(&self.staging_dir, file.filename());
+
+        let mut output = BufWriter::new(File::create(&staging_path)?);
+        let hash = self.write_patched_file(file, &mut output);
+        output.flush()?;
+
+        if !matches!(&hash, Ok(hash) if hash == file.hash()) {
+            // the old install doesn't hold what the old manifest says
+            let mut output = BufWriter::new(File::create(&staging_path)?);
+            let hash = self.reconstructor.write_file(file, &mut output)?;
+            output.flush()?;
+
+            if hash != *file.hash() {
+                return Err(ParseError::HashMismatch);
+            }
+        }
+
+        File::open(&staging_path)?.sync_all()?;
+        Ok(staging_path)
+    }
+
+    fn write_patched_file(&mut self, file: &FFileManifest, mut output: impl Write) -> ParseResult<FSHAHash> {
+        let mut hasher = Sha1::new();
+        let mut buffer = vec![];
+
+        for part in file.chunk_parts() {
+            match self.plan.find_old_part(part).copied() {
+                Some(location) => {
+                    buffer.resize(part.size() as usize, 0);
+                    self.read_old_part(&location, part, &mut buffer)?;
+
+                    hasher.update(&buffer);
+                    output.write_all(&buffer)?;
+                },
+                None => {
+                    let part_data = self.reconstructor.part_data(part)?;
+
+                    hasher.update(part_data);
+                    output.write_all(part_data)?;
+                }
+            }
+        }
+
+        Ok(FSHAHash::new(hasher.finalize().into()))
+    }
+
+    // the last old file read is kept open, consecutive parts usually come from the same file
+    fn read_old_part(&mut self, location: &PartLocation<'a>, part: &FChunkPart, buffer: &mut [u8]) -> ParseResult<()> {
+        if !matches!(&self.old_file, Some((filename, _)) if *filename == location.filename) {
+            let path = install_path(&self.install_dir, location.filename)?;
+            self.old_file = Some((location.filename, File::open(path)?));
+        }
+
+        let (_, input) = self.old_file.as_mut().ok_or(ParseError::InvalidData)?;
+        input.seek(SeekFrom::Start((location.file_offset + (part.offset() - location.offset) as usize) as u64))?;
+        input.read_exact(buffer)?;
+
+        Ok(())
+    }
+
+    // empty directories left by the removed file are removed as well
+    fn remove_file(&self, file: &FFileManifest) -> ParseResult<()> {
+        let path = install_path(&self.install_dir, file.filename())?;
+
+        if !file.is_symlink() {
+            make_writable(&path)?;
+        }
+
+        match fs::remove_file(&path) {
+            Err(error) if error.kind() != ErrorKind::NotFound => return Err(error.into()),
+            _ => {},
+        }
+
+        let mut directory = path.parent();
+        while let Some(parent) = directory.filter(|parent| *parent != self.install_dir) {
+            if fs::remove_dir(parent).is_err() {
+                break;
+            }
+
+            directory = parent.parent();
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{install::{chunk_store::ChunkStore, resume::ResumableInstaller, verify::InstallVerifier}, manifest::{builder::ManifestBuilder, shared::EFileMetaFlags}, test_utils::{random_data, TempDir}};
+
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn retargeted_symlinks_are_patched() {
+        let old_source = TempDir::new("patch_old_source");
+        let new_source = TempDir::new("patch_new_source");
+        let cloud_dir = TempDir::new("patch_cloud");
+        let install_dir = TempDir::new("patch_install");
+
+        for source in [&old_source, &new_source] {
+            source.write("lib/libfoo.so.1", &random_data(2000, 8));
+            source.write("lib/libfoo.so.2", &random_data(3000, 9));
+        }
+        new_source.write("bin/game", &random_data(1000, 10));
+        std::os::unix::fs::symlink("libfoo.so.1", old_source.join("lib/libfoo.so")).unwrap();
+        std::os::unix::fs::symlink("libfoo.so.2", new_source.join("lib/libfoo.so")).unwrap();
+
+        let old_manifest = ManifestBuilder::new("Test", "1").build(old_source.path(), cloud_dir.path()).unwrap();
+        let new_manifest = ManifestBuilder::new("Test", "2").build(new_source.path(), cloud_dir.path()).unwrap();
+        let store = ChunkStore::open(cloud_dir.path()).unwrap();
+        ResumableInstaller::new(&old_manifest, &store, install_dir.path()).install().unwrap();
+
+        // the symlink has the same hash in both builds, only its target changed
+        let plan = PatchPlan::new(&old_manifest, &new_manifest);
+        let mut changed_files: Vec<&str> = plan.changed_files().iter().map(|file| file.filename()).collect();
+        changed_files.sort();
+        assert_eq!(changed_files, ["bin/game", "lib/libfoo.so"]);
+        assert_eq!(plan.diff().modified_files().len(), 1);
+        assert_eq!(plan.unchanged_files().len(), 2);
+
+        PatchInstaller::new(&plan, &store, install_dir.path()).apply().unwrap();
+        assert_eq!(fs::read_link(install_dir.join("lib/libfoo.so")).unwrap(), Path::new("libfoo.so.2"));
+        assert!(InstallVerifier::new(&new_manifest, install_dir.path()).verify().unwrap().is_valid());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn flags_only_changes_are_patched() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let source = TempDir::new("patch_flags_source");
+        let cloud_dir = TempDir::new("patch_flags_cloud");
+        let install_dir = TempDir::new("patch_flags_install");
+
+        source.write("game", &random_data(1000, 20));
+        let mut old_manifest = ManifestBuilder::new("Test", "1").build(source.path(), cloud_dir.path()).unwrap();
+        old_manifest.file_list.entries[0].flags = (EFileMetaFlags::ReadOnly | EFileMetaFlags::UnixExecutable).bits();
+
+        // the new build only loses the attributes of the file
+        let mut new_manifest = old_manifest.clone();
+        new_manifest.file_list.entries[0].flags = EFileMetaFlags::empty().bits();
+
+        let store = ChunkStore::open(cloud_dir.path()).unwrap();
+        ResumableInstaller::new(&old_manifest, &store, install_dir.path()).install().unwrap();
+        let mode = || fs::metadata(install_dir.join("game")).unwrap().permissions().mode();
+        assert_eq!(mode() & 0o333, 0o111);
+
+        let plan = PatchPlan::new(&old_manifest, &new_manifest);
+        assert_eq!(plan.changed_files().len(), 1);
+        assert!(plan.required_chunks().is_empty());
+
+        PatchInstaller::new(&plan, &store, install_dir.path()).apply().unwrap();
+        assert_eq!(mode() & 0o311, 0o200);
+    }
+}
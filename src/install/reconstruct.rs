@@ -0,0 +1,162 @@
+use std::{collections::HashMap, fs::{self, File}, io::{BufWriter, Write}, path::{Path, PathBuf}};
+
+use sha1::{Digest, Sha1};
+
+use crate::{error::ParseError, manifest::{chunk_info::FChunkInfo, chunk_part::FChunkPart, file_manifest::FFileManifest, shared::{FGuid, FSHAHash}, FManifest}, ParseResult};
+
+use super::{apply_file_flags, chunk_source::ChunkSource, create_symlink, install_path, make_writable, remove_symlink};
+
+/// This type is used to rebuild the files of a manifest from the chunks of a ChunkSource, such as a ChunkStore.
+/// The last decoded chunk is kept, as consecutive chunk parts and small files often come from the same chunk.
+pub struct FileReconstructor<'a> {
+    manifest: &'a FManifest,
+    source: &'a dyn ChunkSource,
+    chunks: HashMap<FGuid, &'a FChunkInfo>,
+    cached_chunk: Option<(FGuid, Vec<u8>)>,
+}
+
+impl<'a> FileReconstructor<'a> {
+    pub fn new(manifest: &'a FManifest, source: &'a dyn ChunkSource) -> FileReconstructor<'a> {
+        FileReconstructor {
+            manifest,
+            source,
+            chunks: manifest.chunk_list.chunks().iter().map(|chunk| (*chunk.guid(), chunk)).collect(),
+            cached_chunk: None,
+        }
+    }
+
+    /// This function is used to rebuild every file of the manifest into `install_dir`
+    pub fn reconstruct_all(&mut self, install_dir: impl AsRef<Path>) -> ParseResult<()> {
+        let manifest = self.manifest;
+
+        for file in manifest.file_list.entries() {
+            self.reconstruct_file(file, install_dir.as_ref())?;
+        }
+
+        Ok(())
+    }
+
+    /// This function is used to rebuild a single file into `install_dir`, the written file is verified against `FFileManifest::hash`
+    /// and its attributes are applied once it is complete. Symlinks are created instead, their target must stay inside of `install_dir`.
+    pub fn reconstruct_file(&mut self, file: &FFileManifest, install_dir: &Path) -> ParseResult<PathBuf> {
+        let path = install_path(install_dir, file.filename())?;
+
+        if file.is_symlink() {
+            create_symlink(install_dir, &path, file.syslink_target())?;
+            return Ok(path);
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        remove_symlink(&path)?;
+        make_writable(&path)?;
+        let mut output = BufWriter::new(File::create(&path)?);
+        let hash = self.write_file(file, &mut output)?;
+        output.flush()?;
+        drop(output);
+
+        if &hash != file.hash() {
+            return Err(ParseError::HashMismatch);
+        }
+
+        apply_file_flags(&path, file.flags())?;
+
+        Ok(path)
+    }
+
+    /// This function is used to write the content of a file into any writer by walking its chunk parts.
+    /// The SHA1 of the written data is returned.
+    pub fn write_file(&mut self, file: &FFileManifest, mut output: impl Write) -> ParseResult<FSHAHash> {
+        let mut hasher = Sha1::new();
+
+        for part in file.chunk_parts() {
+            let part_data = self.part_data(part)?;
+
+            hasher.update(part_data);
+            output.write_all(part_data)?;
+        }
+
+        Ok(FSHAHash::new(hasher.finalize().into()))
+    }
+
+    /// This function is used to get the data of a chunk part, its chunk is read from the store unless it is the last one used
+    pub(crate) fn part_data(&mut self, part: &FChunkPart) -> ParseResult<&[u8]> {
+        let start = part.offset() as usize;
+        let end = start + part.size() as usize;
+
+        self.chunk_data(part.guid())?.get(start..end).ok_or(ParseError::Overflow)
+    }
+
+    fn chunk_data(&mut self, guid: &FGuid) -> ParseResult<&[u8]> {
+        if !matches!(&self.cached_chunk, Some((cached_guid, _)) if cached_guid == guid) {
+            let chunk_info = self.chunks.get(guid).ok_or(ParseError::MissingChunk(*guid))?;
+            let chunk = self.source.fetch_chunk(chunk_info)?;
+            self.cached_chunk = Some((*guid, chunk.into_data()));
+        }
+
+        Ok(self.cached_chunk.as_ref().map(|(_, data)| data.as_slice()).unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{install::chunk_store::ChunkStore, manifest::builder::{EChunkingMode, ManifestBuilder}, test_utils::{random_data, TempDir}};
+
+    use super::*;
+
+    fn build(source: &TempDir, cloud_dir: &TempDir) -> FManifest {
+        source.write("Game.exe", &random_data(200 * 1024, 31));
+        source.write("Content/data.pak", &random_data(150 * 1024, 32));
+        source.write("Content/empty.txt", &[]);
+
+        ManifestBuilder::new("Test", "1").with_chunking(EChunkingMode::Fixed, 64 * 1024).build(source.path(), cloud_dir.path()).unwrap()
+    }
+
+    #[test]
+    fn files_are_reconstructed_from_their_chunks() {
+        let source = TempDir::new("reconstruct_source");
+        let cloud_dir = TempDir::new("reconstruct_cloud");
+        let install_dir = TempDir::new("reconstruct_install");
+        let manifest = build(&source, &cloud_dir);
+        let store = ChunkStore::open(cloud_dir.path()).unwrap();
+
+        let game = manifest.file_list.entries().iter().find(|file| file.filename() == "Game.exe").unwrap();
+        assert!(game.chunk_parts().len() > 1);
+
+        let mut reconstructor = FileReconstructor::new(&manifest, &store);
+        reconstructor.reconstruct_all(install_dir.path()).unwrap();
+
+        for file in manifest.file_list.entries() {
+            assert_eq!(fs::read(install_dir.join(file.filename())).unwrap(), fs::read(source.join(file.filename())).unwrap(), "{}", file.filename());
+        }
+
+        let mut output = vec![];
+        assert_eq!(&reconstructor.write_file(game, &mut output).unwrap(), game.hash());
+        assert_eq!(output, fs::read(source.join("Game.exe")).unwrap());
+    }
+
+    #[test]
+    fn files_not_matching_their_hash_are_rejected() {
+        let source = TempDir::new("reconstruct_mismatch_source");
+        let cloud_dir = TempDir::new("reconstruct_mismatch_cloud");
+        let install_dir = TempDir::new("reconstruct_mismatch_install");
+        let mut manifest = build(&source, &cloud_dir);
+        let store = ChunkStore::open(cloud_dir.path()).unwrap();
+
+        // the first two parts of the file swapped: every chunk is valid but the file isn't
+        let game = manifest.file_list.entries.iter_mut().find(|file| file.filename() == "Game.exe").unwrap();
+        let (first, second) = (game.chunk_parts[0].guid, game.chunk_parts[1].guid);
+        game.chunk_parts[0].guid = second;
+        game.chunk_parts[1].guid = first;
+
+        let game = manifest.file_list.entries().iter().find(|file| file.filename() == "Game.exe").unwrap();
+        let mut reconstructor = FileReconstructor::new(&manifest, &store);
+        assert!(matches!(reconstructor.reconstruct_file(game, install_dir.path()), Err(ParseError::HashMismatch)));
+
+        manifest.chunk_list.chunks.clear();
+        let mut reconstructor = FileReconstructor::new(&manifest, &store);
+        assert!(matches!(reconstructor.reconstruct_all(install_dir.path()), Err(ParseError::MissingChunk(_))));
+    }
+}
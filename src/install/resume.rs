@@ -0,0 +1,419 @@
+use std::{collections::BTreeMap, fs::{self, File, OpenOptions}, io::{ErrorKind, Read, Seek, SeekFrom, Write}, path::{Path, PathBuf}};
+
+use sha1::{Digest, Sha1};
+
+use crate::{error::ParseError, manifest::{chunk_part::FChunkPart, file_manifest::FFileManifest, shared::FSHAHash, FManifest}, ParseResult};
+
+use super::{apply_file_flags, chunk_source::ChunkSource, create_symlink, install_path, make_writable, reconstruct::FileReconstructor, remove_symlink};
+
+/// Name of the directory created in the install directory to hold the files being written and the state of the install
+pub const STAGING_DIRECTORY: &str = ".staging";
+/// Name of the state file, stored in the staging directory
+pub const STATE_FILENAME: &str = "state.json";
+/// Default amount of bytes written between two saves of the state
+pub const DEFAULT_CHECKPOINT_INTERVAL: u64 = 64 * 1024 * 1024;
+
+const STAGING_EXTENSION: &str = "partial";
+const READ_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// Progress of a single file of the manifest
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct FileProgress {
+    hash: FSHAHash,
+    completed: bool,
+    written_parts: BTreeMap<usize, FSHAHash>,
+}
+
+impl FileProgress {
+    /// Hash of the file this progress was recorded for
+    pub fn hash(&self) -> &FSHAHash {
+        &self.hash
+    }
+
+    /// True once the file was moved to its final path
+    pub fn is_completed(&self) -> bool {
+        self.completed
+    }
+
+    /// SHA1 of the chunk parts already written to the staging file, keyed by `FChunkPart::file_offset`
+    pub fn written_parts(&self) -> &BTreeMap<usize, FSHAHash> {
+        &self.written_parts
+    }
+}
+
+/// This type is the progress of an install, it is saved to disk so an interrupted install can be resumed.
+/// Files are keyed by `FFileManifest::filename`.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct InstallState {
+    build_id: String,
+    files: BTreeMap<String, FileProgress>,
+}
+
+impl InstallState {
+    pub fn new(manifest: &FManifest) -> InstallState {
+        InstallState {
+            build_id: manifest.meta.effective_build_id(),
+            files: BTreeMap::new(),
+        }
+    }
+
+    /// This function is used to read a state file, `None` is returned when there is no state file
+    pub fn load(path: impl AsRef<Path>) -> ParseResult<Option<InstallState>> {
+        let data = match fs::read(path) {
+            Ok(data) => data,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error.into()),
+        };
+
+        serde_json::from_slice(&data).map(Some).map_err(|_| ParseError::InvalidJson)
+    }
+
+    /// This function is used to save the state, the file is replaced at once so a crash never leaves a half written state
+    pub fn save(&self, path: impl AsRef<Path>) -> ParseResult<()> {
+        let path = path.as_ref();
+        let data = serde_json::to_vec(self).map_err(|_| ParseError::InvalidJson)?;
+
+        let temporary_path = path.with_extension("tmp");
+        let mut output = File::create(&temporary_path)?;
+        output.write_all(&data)?;
+        output.sync_all()?;
+        drop(output);
+
+        fs::rename(&temporary_path, path)?;
+        Ok(())
+    }
+
+    /// Build id of the manifest being installed, a state of another build is never resumed
+    pub fn build_id(&self) -> &str {
+        &self.build_id
+    }
+
+    pub fn file(&self, filename: &str) -> Option<&FileProgress> {
+        self.files.get(filename)
+    }
+
+    fn is_completed(&self, file: &FFileManifest) -> bool {
+        self.file(file.filename()).is_some_and(|progress| progress.completed && progress.hash == *file.hash())
+    }
+
+    // the progress of a file that changed since the state was saved is discarded
+    fn file_mut(&mut self, file: &FFileManifest) -> &mut FileProgress {
+        let progress = self.files.entry(file.filename().to_owned()).or_default();
+
+        if progress.hash != *file.hash() {
+            *progress = FileProgress {
+                hash: file.hash().clone(),
+                ..Default::default()
+            };
+        }
+
+        progress
+    }
+}
+
+/// This type is used to install the files of a manifest so the install can be resumed after a crash.
+/// Files are written to a staging file first and moved to their final path once verified, the progress of every chunk part
+/// is saved to a state file. When resuming, the chunk parts already written are checked against the hash saved with them.
+pub struct ResumableInstaller<'a> {
+    manifest: &'a FManifest,
+    reconstructor: FileReconstructor<'a>,
+    install_dir: PathBuf,
+    staging_dir: PathBuf,
+    checkpoint_interval: u64,
+    written_since_checkpoint: u64,
+    state: InstallState,
+}
+
+impl<'a> ResumableInstaller<'a> {
+    pub fn new(manifest: &'a FManifest, source: &'a dyn ChunkSource, install_dir: impl AsRef<Path>) -> ResumableInstaller<'a> {
+        let install_dir = install_dir.as_ref().to_path_buf();
+
+        ResumableInstaller {
+            manifest,
+            reconstructor: FileReconstructor::new(manifest, source),
+            staging_dir: install_dir.join(STAGING_DIRECTORY),
+            install_dir,
+            checkpoint_interval: DEFAULT_CHECKPOINT_INTERVAL,
+            written_since_checkpoint: 0,
+            state: InstallState::new(manifest),
+        }
+    }
+
+    /// This function is used to write the staging files and the state somewhere else than in the install directory
+    pub fn with_staging_dir(mut self, staging_dir: impl AsRef<Path>) -> ResumableInstaller<'a> {
+        self.staging_dir = staging_dir.as_ref().to_path_buf();
+        self
+    }
+
+    /// This function is used to set the amount of bytes written between two saves of the state.
+    /// A smaller interval loses less progress on a crash but syncs the disk more often.
+    pub fn with_checkpoint_interval(mut self, checkpoint_interval: u64) -> ResumableInstaller<'a> {
+        self.checkpoint_interval = checkpoint_interval;
+        self
+    }
+
+    pub fn state(&self) -> &InstallState {
+        &self.state
+    }
+
+    /// This function is used to install every file of the manifest, the previous install is resumed if its state file is found.
+    /// Files already installed with the right hash are kept. The state file is removed once every file is installed.
+    pub fn install(&mut self) -> ParseResult<()> {
+        fs::create_dir_all(&self.staging_dir)?;
+
+        // an unreadable state is treated like a missing one, the staging files are verified anyway
+        self.state = match InstallState::load(self.state_path()) {
+            Ok(Some(state)) if state.build_id == self.state.build_id => state,
+            _ => InstallState::new(self.manifest),
+        };
+
+        let manifest = self.manifest;
+        for file in manifest.file_list.entries() {
+            if let Err(error) = self.install_file(file) {
+                // the files installed since the last checkpoint don't have to be verified again on the next attempt
+                let _ = self.save_state();
+                return Err(error);
+            }
+        }
+
+        match fs::remove_file(self.state_path()) {
+            Err(error) if error.kind() != ErrorKind::NotFound => return Err(error.into()),
+            _ => {},
+        }
+
+        // the staging directory is kept if it holds anything else
+        let _ = fs::remove_dir(&self.staging_dir);
+
+        Ok(())
+    }
+
+    fn install_file(&mut self, file: &FFileManifest) -> ParseResult<()> {
+        let path = install_path(&self.install_dir, file.filename())?;
+
+        if self.state.is_completed(file) && fs::symlink_metadata(&path).is_ok() {
+            return Ok(());
+        }
+
+        if file.is_symlink() {
+            create_symlink(&self.install_dir, &path, file.syslink_target())?;
+        } else {
+            let staging_path = self.staging_path(file);
+
+            // the install may have stopped after the file was moved but before the state was saved
+            if staging_path.exists() || !is_installed(&path, file)? {
+                self.write_staging_file(file, &staging_path)?;
+
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+
+                remove_symlink(&path)?;
+                make_writable(&path)?;
+                fs::rename(&staging_path, &path)?;
+            }
+
+            apply_file_flags(&path, file.flags())?;
+            self.written_since_checkpoint += file.file_size();
+        }
+
+        let progress = self.state.file_mut(file);
+        progress.completed = true;
+        progress.written_parts.clear();
+
+        if self.written_since_checkpoint >= self.checkpoint_interval {
+            self.save_state()?;
+        }
+
+        Ok(())
+    }
+
+    fn write_staging_file(&mut self, file: &FFileManifest, staging_path: &Path) -> ParseResult<()> {
+        let mut output = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(staging_path)?;
+
+        // parts written before the interruption are kept only if their data is intact
+        let written_parts = std::mem::take(&mut self.state.file_mut(file).written_parts);
+        let mut verified_parts = BTreeMap::new();
+        for part in file.chunk_parts() {
+            if let Some(hash) = written_parts.get(&part.file_offset()) {
+                if hash_range(&mut output, part.file_offset() as u64, part.size() as u64)? == *hash {
+                    verified_parts.insert(part.file_offset(), hash.clone());
+                }
+            }
+        }
+
+        self.state.file_mut(file).written_parts = verified_parts;
+        output.set_len(file.file_size())?;
+
+        for part in file.chunk_parts() {
+            if self.state.file_mut(file).written_parts.contains_key(&part.file_offset()) {
+                continue;
+            }
+
+            let hash = match self.write_part(&mut output, part) {
+                Ok(hash) => hash,
+                Err(error) => {
+                    // the parts written so far are kept for the next attempt, as long as they are on the disk
+                    if output.sync_data().is_ok() {
+                        let _ = self.save_state();
+                    }
+
+                    return Err(error);
+                }
+            };
+
+            self.state.file_mut(file).written_parts.insert(part.file_offset(), hash);
+            self.written_since_checkpoint += part.size() as u64;
+
+            if self.written_since_checkpoint >= self.checkpoint_interval {
+                // the data has to be on the disk before the state says it is written
+                output.sync_data()?;
+                self.save_state()?;
+            }
+        }
+
+        output.sync_data()?;
+
+        if hash_range(&mut output, 0, file.file_size())? != *file.hash() {
+            drop(output);
+            fs::remove_file(staging_path)?;
+            self.state.file_mut(file).written_parts.clear();
+
+            return Err(ParseError::HashMismatch);
+        }
+
+        Ok(())
+    }
+
+    fn write_part(&mut self, output: &mut File, part: &FChunkPart) -> ParseResult<FSHAHash> {
+        let part_data = self.reconstructor.part_data(part)?;
+        let hash = FSHAHash::new(Sha1::digest(part_data).into());
+
+        output.seek(SeekFrom::Start(part.file_offset() as u64))?;
+        output.write_all(part_data)?;
+
+        Ok(hash)
+    }
+
+    fn save_state(&mut self) -> ParseResult<()> {
+        self.written_since_checkpoint = 0;
+        self.state.save(self.state_path())
+    }
+
+    fn state_path(&self) -> PathBuf {
+        self.staging_dir.join(STATE_FILENAME)
+    }
+
+    fn staging_path(&self, file: &FFileManifest) -> PathBuf {
+        staging_path(&self.staging_dir, file.filename())
+    }
+}
+
+/// This function is used to get the path a file is written to before being moved to its final path.
+/// Staging files are named after the SHA1 of the filename so the staging directory stays flat.
+pub(crate) fn staging_path(staging_dir: &Path, filename: &str) -> PathBuf {
+    let name = FSHAHash::new_from_hashable(filename).to_string();
+    staging_dir.join(format!("{}.{}", name, STAGING_EXTENSION))
+}
+
+fn is_installed(path: &Path, file: &FFileManifest) -> ParseResult<bool> {
+    let mut input = match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_file() && metadata.len() == file.file_size() => File::open(path)?,
+        _ => return Ok(false),
+    };
+
+    Ok(hash_range(&mut input, 0, file.file_size())? == *file.hash())
+}
+
+fn hash_range(input: &mut File, offset: u64, size: u64) -> ParseResult<FSHAHash> {
+    let mut hasher = Sha1::new();
+    let mut buffer = vec![0u8; READ_BUFFER_SIZE.min(size as usize)];
+    let mut remaining = size;
+
+    input.seek(SeekFrom::Start(offset))?;
+    while remaining > 0 {
+        let length = input.read(&mut buffer[..READ_BUFFER_SIZE.min(remaining as usize)])?;
+        if length == 0 {
+            break;
+        }
+
+        hasher.update(&buffer[..length]);
+        remaining -= length as u64;
+    }
+
+    Ok(FSHAHash::new(hasher.finalize().into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::{install::{chunk_store::ChunkStore, verify::InstallVerifier}, manifest::{builder::{EChunkingMode, ManifestBuilder}, chunk_info::FChunkInfo, chunks::chunk::FChunk}, test_utils::{random_data, TempDir}};
+
+    use super::*;
+
+    // a source that stops working after a given amount of chunks, like a connection lost during an install
+    struct InterruptedSource {
+        store: ChunkStore,
+        remaining: AtomicUsize,
+        fetched: AtomicUsize,
+    }
+
+    impl InterruptedSource {
+        fn new(store: ChunkStore, limit: usize) -> InterruptedSource {
+            InterruptedSource {
+                store,
+                remaining: AtomicUsize::new(limit),
+                fetched: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    impl ChunkSource for InterruptedSource {
+        fn fetch_chunk(&self, chunk: &FChunkInfo) -> ParseResult<FChunk> {
+            self.remaining.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |remaining| remaining.checked_sub(1))
+                .map_err(|_| ParseError::MissingChunk(*chunk.guid()))?;
+            self.fetched.fetch_add(1, Ordering::Relaxed);
+
+            self.store.fetch_chunk(chunk)
+        }
+
+        fn has_chunk(&self, chunk: &FChunkInfo) -> bool {
+            self.store.has_chunk(chunk)
+        }
+    }
+
+    #[test]
+    fn interrupted_install_is_resumed() {
+        let source = TempDir::new("resume_source");
+        let cloud_dir = TempDir::new("resume_cloud");
+        let install_dir = TempDir::new("resume_install");
+
+        source.write("small.bin", &random_data(1000, 4));
+        source.write("data/large.bin", &random_data(16 * 64 * 1024, 5));
+
+        let manifest = ManifestBuilder::new("Test", "1").with_chunking(EChunkingMode::Fixed, 64 * 1024).build(source.path(), cloud_dir.path()).unwrap();
+        let chunk_count = manifest.chunk_list.chunks().len();
+
+        // no checkpoint is due, the state is only saved because of the failure
+        let interrupted = InterruptedSource::new(ChunkStore::open(cloud_dir.path()).unwrap(), 6);
+        let mut installer = ResumableInstaller::new(&manifest, &interrupted, install_dir.path()).with_checkpoint_interval(u64::MAX);
+        assert!(matches!(installer.install(), Err(ParseError::MissingChunk(_))));
+
+        let state = InstallState::load(install_dir.join(STAGING_DIRECTORY).join(STATE_FILENAME)).unwrap().unwrap();
+        let written_parts: usize = manifest.file_list.entries().iter()
+            .filter_map(|file| state.file(file.filename()))
+            .map(|progress| progress.written_parts().len())
+            .sum();
+        assert!(written_parts > 0);
+
+        // the staging directory of the unfinished install isn't reported as extra files
+        assert!(InstallVerifier::new(&manifest, install_dir.path()).verify().unwrap().extra_files().is_empty());
+
+        let resumed = InterruptedSource::new(ChunkStore::open(cloud_dir.path()).unwrap(), usize::MAX);
+        ResumableInstaller::new(&manifest, &resumed, install_dir.path()).install().unwrap();
+
+        assert!(resumed.fetched.load(Ordering::Relaxed) < chunk_count);
+        assert!(!install_dir.join(STAGING_DIRECTORY).exists());
+        assert!(InstallVerifier::new(&manifest, install_dir.path()).verify().unwrap().is_valid());
+    }
+}
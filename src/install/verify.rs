@@ -0,0 +1,301 @@
+use std::{collections::HashSet, fs::{self, File}, io::Read, path::{Path, PathBuf}};
+
+use md5::Md5;
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+
+use crate::{error::ParseError, manifest::{chunk_info::FChunkInfo, file_manifest::FFileManifest, shared::FGuid, FManifest}, ParseResult};
+
+use super::{install_path, resume::STAGING_DIRECTORY, symlink_target_path};
+
+const READ_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// Result of the verification of a single file
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+pub enum EFileVerifyResult {
+    Valid,
+    Missing,
+    SizeMismatch { expected: u64, actual: u64 },
+    Sha1Mismatch,
+    Sha256Mismatch,
+    Md5Mismatch,
+    /// The file is not a symlink while the manifest expects one (or the opposite), or the symlink has another target
+    SymlinkMismatch,
+    /// The symlink points outside of the install directory, the installer never creates such a symlink
+    SymlinkOutsideOfInstall,
+}
+
+impl EFileVerifyResult {
+    pub fn is_valid(&self) -> bool {
+        *self == EFileVerifyResult::Valid
+    }
+
+    pub fn is_corrupt(&self) -> bool {
+        !matches!(self, EFileVerifyResult::Valid | EFileVerifyResult::Missing)
+    }
+}
+
+/// This type is the result of the verification of an install directory
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct VerifyReport<'a> {
+    files: Vec<(&'a FFileManifest, EFileVerifyResult)>,
+    extra_files: Vec<String>,
+    chunks_to_repair: Vec<&'a FChunkInfo>,
+}
+
+impl<'a> VerifyReport<'a> {
+    /// Every file of the manifest along with its verification result
+    pub fn files(&self) -> &[(&'a FFileManifest, EFileVerifyResult)] {
+        &self.files
+    }
+
+    pub fn missing_files(&self) -> impl Iterator<Item = &'a FFileManifest> + '_ {
+        self.files.iter().filter(|(_, result)| *result == EFileVerifyResult::Missing).map(|(file, _)| *file)
+    }
+
+    pub fn corrupt_files(&self) -> impl Iterator<Item = (&'a FFileManifest, EFileVerifyResult)> + '_ {
+        self.files.iter().filter(|(_, result)| result.is_corrupt()).copied()
+    }
+
+    /// Files found in the install directory that are not part of the manifest, relative to the install directory and separated by `/`
+    pub fn extra_files(&self) -> &[String] {
+        &self.extra_files
+    }
+
+    /// Chunks needed to rewrite the missing and corrupt files
+    pub fn chunks_to_repair(&self) -> &[&'a FChunkInfo] {
+        &self.chunks_to_repair
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.files.iter().all(|(_, result)| result.is_valid())
+    }
+}
+
+/// This type is used to check an install directory against a manifest, like the "verify" button of the launcher
+pub struct InstallVerifier<'a> {
+    manifest: &'a FManifest,
+    install_dir: PathBuf,
+}
+
+impl<'a> InstallVerifier<'a> {
+    pub fn new(manifest: &'a FManifest, install_dir: impl AsRef<Path>) -> InstallVerifier<'a> {
+        InstallVerifier {
+            manifest,
+            install_dir: install_dir.as_ref().to_path_buf(),
+        }
+    }
+
+    /// This function is used to verify every file of the manifest and to look for files that are not part of it
+    pub fn verify(&self) -> ParseResult<VerifyReport<'a>> {
+        let manifest = self.manifest;
+
+        let mut files = Vec::with_capacity(manifest.file_list.entries().len());
+        for file in manifest.file_list.entries() {
+            files.push((file, self.verify_file(file)?));
+        }
+
+        let repair_guids: HashSet<&FGuid> = files.iter()
+            .filter(|(_, result)| !result.is_valid())
+            .flat_map(|(file, _)| file.chunk_parts())
+            .map(|part| part.guid())
+            .collect();
+
+        let chunks_to_repair = manifest.chunk_list.chunks().iter().filter(|chunk| repair_guids.contains(chunk.guid())).collect();
+
+        Ok(VerifyReport {
+            files,
+            extra_files: self.find_extra_files()?,
+            chunks_to_repair,
+        })
+    }
+
+    /// This function is used to verify a single file: its size first, then its SHA1, and its SHA256 and MD5 when the manifest has them.
+    /// Symlinks are verified by their target, which must stay inside of the install directory.
+    pub fn verify_file(&self, file: &FFileManifest) -> ParseResult<EFileVerifyResult> {
+        let path = install_path(&self.install_dir, file.filename())?;
+
+        let metadata = match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.file_type().is_symlink() && file.is_symlink() => return self.verify_symlink(file, &path),
+            Ok(metadata) if metadata.file_type().is_symlink() || file.is_symlink() => return Ok(EFileVerifyResult::SymlinkMismatch),
+            Ok(metadata) if metadata.is_file() => metadata,
+            _ => return Ok(EFileVerifyResult::Missing),
+        };
+
+        if metadata.len() != file.file_size() {
+            return Ok(EFileVerifyResult::SizeMismatch {
+                expected: file.file_size(),
+                actual: metadata.len(),
+            });
+        }
+
+        let sha256 = file.sha256_hash().filter(|hash| hash.data().iter().any(|byte| *byte != 0));
+        let md5 = file.md5_hash();
+
+        let mut sha1_hasher = Sha1::new();
+        let mut sha256_hasher = sha256.map(|_| Sha256::new());
+        let mut md5_hasher = md5.map(|_| Md5::new());
+
+        let mut reader = File::open(&path)?;
+        let mut buffer = vec![0u8; READ_BUFFER_SIZE];
+        loop {
+            let length = reader.read(&mut buffer)?;
+            if length == 0 {
+                break;
+            }
+
+            sha1_hasher.update(&buffer[..length]);
+            if let Some(hasher) = sha256_hasher.as_mut() {
+                hasher.update(&buffer[..length]);
+            }
+            if let Some(hasher) = md5_hasher.as_mut() {
+                hasher.update(&buffer[..length]);
+            }
+        }
+
+        if sha1_hasher.finalize().as_slice() != file.sha_hash().data() {
+            return Ok(EFileVerifyResult::Sha1Mismatch);
+        }
+
+        if let (Some(hash), Some(hasher)) = (sha256, sha256_hasher) {
+            if hasher.finalize().as_slice() != hash.data() {
+                return Ok(EFileVerifyResult::Sha256Mismatch);
+            }
+        }
+
+        if let (Some(hash), Some(hasher)) = (md5, md5_hasher) {
+            if hasher.finalize().as_slice() != hash.data() {
+                return Ok(EFileVerifyResult::Md5Mismatch);
+            }
+        }
+
+        Ok(EFileVerifyResult::Valid)
+    }
+
+    fn verify_symlink(&self, file: &FFileManifest, path: &Path) -> ParseResult<EFileVerifyResult> {
+        match symlink_target_path(&self.install_dir, path, file.syslink_target()) {
+            Err(ParseError::InvalidPath(_)) => return Ok(EFileVerifyResult::SymlinkOutsideOfInstall),
+            result => result?,
+        };
+
+        // targets are compared with `/` as separator, like they are stored in the manifest
+        let target = fs::read_link(path)?.to_string_lossy().replace('\\', "/");
+        if target.trim_end_matches('/') != file.syslink_target().replace('\\', "/").trim_end_matches('/') {
+            return Ok(EFileVerifyResult::SymlinkMismatch);
+        }
+
+        Ok(EFileVerifyResult::Valid)
+    }
+
+    fn find_extra_files(&self) -> ParseResult<Vec<String>> {
+        let known_files: HashSet<&str> = self.manifest.file_list.entries().iter().map(|file| file.filename()).collect();
+
+        let mut extra_files = vec![];
+        let mut directories = vec![(self.install_dir.clone(), String::new())];
+
+        while let Some((directory, prefix)) = directories.pop() {
+            let entries = match fs::read_dir(&directory) {
+                Ok(entries) => entries,
+                Err(_) if directory == self.install_dir => return Ok(extra_files),
+                Err(error) => return Err(error.into()),
+            };
+
+            for entry in entries {
+                let entry = entry?;
+                let name = prefix.clone() + &entry.file_name().to_string_lossy();
+
+                if entry.file_type()?.is_dir() {
+                    // the staging directory of an unfinished install isn't part of the build
+                    if name == STAGING_DIRECTORY {
+                        continue;
+                    }
+
+                    directories.push((entry.path(), name + "/"));
+                } else if !known_files.contains(name.as_str()) {
+                    extra_files.push(name);
+                }
+            }
+        }
+
+        extra_files.sort();
+        Ok(extra_files)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{install::{chunk_store::ChunkStore, resume::ResumableInstaller}, manifest::builder::{EChunkingMode, ManifestBuilder}, test_utils::{random_data, TempDir}};
+
+    use super::*;
+
+    #[test]
+    fn damaged_files_are_reported() {
+        let install_dir = TempDir::new("verify_damaged_install");
+        let cloud_dir = TempDir::new("verify_damaged_cloud");
+
+        // every file fills whole chunks, so the chunks of a file are not shared with the others
+        install_dir.write("missing.bin", &random_data(64 * 1024, 26));
+        install_dir.write("truncated.bin", &random_data(128 * 1024, 27));
+        install_dir.write("modified.bin", &random_data(64 * 1024, 28));
+        install_dir.write("data/valid.bin", &random_data(64 * 1024, 29));
+
+        let manifest = ManifestBuilder::new("Test", "1").with_chunking(EChunkingMode::Fixed, 64 * 1024).build(install_dir.path(), cloud_dir.path()).unwrap();
+        assert!(InstallVerifier::new(&manifest, install_dir.path()).verify().unwrap().is_valid());
+
+        fs::remove_file(install_dir.join("missing.bin")).unwrap();
+        fs::write(install_dir.join("truncated.bin"), random_data(100, 27)).unwrap();
+        let mut data = fs::read(install_dir.join("modified.bin")).unwrap();
+        data[1000] ^= 0xFF;
+        fs::write(install_dir.join("modified.bin"), data).unwrap();
+        install_dir.write("extra.txt", b"extra");
+        install_dir.write("data/logs/extra.log", b"extra");
+
+        let report = InstallVerifier::new(&manifest, install_dir.path()).verify().unwrap();
+        let result = |filename: &str| report.files().iter().find(|(file, _)| file.filename() == filename).unwrap().1;
+
+        assert!(!report.is_valid());
+        assert_eq!(result("missing.bin"), EFileVerifyResult::Missing);
+        assert_eq!(result("truncated.bin"), EFileVerifyResult::SizeMismatch { expected: 128 * 1024, actual: 100 });
+        assert_eq!(result("modified.bin"), EFileVerifyResult::Sha1Mismatch);
+        assert_eq!(result("data/valid.bin"), EFileVerifyResult::Valid);
+        assert_eq!(report.missing_files().map(|file| file.filename()).collect::<Vec<_>>(), ["missing.bin"]);
+        assert_eq!(report.corrupt_files().count(), 2);
+        assert_eq!(report.extra_files(), ["data/logs/extra.log", "extra.txt"]);
+
+        let repair_chunks: HashSet<&FGuid> = manifest.file_list.entries().iter()
+            .filter(|file| file.filename() != "data/valid.bin")
+            .flat_map(|file| file.chunk_parts())
+            .map(|part| part.guid())
+            .collect();
+        assert_eq!(report.chunks_to_repair().len(), 4);
+        assert!(report.chunks_to_repair().iter().all(|chunk| repair_chunks.contains(chunk.guid())));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_outside_of_the_install_are_reported() {
+        let source = TempDir::new("verify_source");
+        let cloud_dir = TempDir::new("verify_cloud");
+        let install_dir = TempDir::new("verify_install");
+
+        source.write("bin/game", b"game");
+        std::os::unix::fs::symlink("game", source.join("bin/launcher")).unwrap();
+
+        let mut manifest = ManifestBuilder::new("Test", "1").build(source.path(), cloud_dir.path()).unwrap();
+        let store = ChunkStore::open(cloud_dir.path()).unwrap();
+        ResumableInstaller::new(&manifest, &store, install_dir.path()).install().unwrap();
+        assert!(InstallVerifier::new(&manifest, install_dir.path()).verify().unwrap().is_valid());
+
+        // a tampered install: the symlink now points outside of the install directory
+        let link = manifest.file_list.entries.iter_mut().find(|file| file.is_symlink()).unwrap();
+        link.syslink_target = "../../outside".to_owned();
+        fs::remove_file(install_dir.join("bin/launcher")).unwrap();
+        std::os::unix::fs::symlink("../../outside", install_dir.join("bin/launcher")).unwrap();
+
+        let report = InstallVerifier::new(&manifest, install_dir.path()).verify().unwrap();
+        for (file, result) in report.files() {
+            let expected = if file.is_symlink() { EFileVerifyResult::SymlinkOutsideOfInstall } else { EFileVerifyResult::Valid };
+            assert_eq!(*result, expected, "{}", file.filename());
+        }
+    }
+}
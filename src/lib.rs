@@ -1,6 +1,12 @@
 pub mod manifest;
 pub mod reader;
+pub mod writer;
 pub mod error;
 pub mod helper;
+pub mod rolling_hash;
+pub mod install;
+pub mod crypto;
+#[cfg(test)]
+mod test_utils;
 
 pub type ParseResult<T> = Result<T, error::ParseError>;
\ No newline at end of file
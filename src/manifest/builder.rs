@@ -0,0 +1,503 @@
+use std::{collections::HashMap, fs::{self, File}, io::{BufReader, Read}, path::{Path, PathBuf}};
+
+use indexmap::IndexMap;
+use sha1::{Digest, Sha1};
+
+use crate::{error::ParseError, rolling_hash::FRollingHash, ParseResult};
+
+use super::{
+    chunk_info::{chunk_path, data_group_number, FChunkInfo},
+    chunk_list::FChunkList,
+    chunk_part::FChunkPart,
+    chunks::chunk::FChunk,
+    custom_fields::FCustomFields,
+    file_manifest::FFileManifest,
+    file_manifest_list::FFileManifestList,
+    header::{FManifestHeader, MANIFEST_HEADER_SIZE, MANIFEST_MAGIC},
+    meta::FManifestMeta,
+    shared::{EFeatureLevel, EFileMetaFlags, EManifestStorageFlags, FGuid, FSHAHash},
+    FManifest, FManifestParser, ManifestWriter,
+};
+
+/// Size of the chunks produced by Epic's tooling
+pub const DEFAULT_CHUNK_WINDOW_SIZE: usize = 1024 * 1024;
+
+/// Amount of bytes the rolling hash looks at to find the boundaries of variable size chunks
+const VARIABLE_CHUNK_HASH_WINDOW: usize = 64;
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// How the files of a build are split into chunks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EChunkingMode {
+    /// Files are packed one after the other into chunks of the window size, small files share chunks
+    Fixed,
+    /// Each file is split where the rolling hash of its content matches a pattern, with chunks between a quarter of the window size and the window size.
+    /// Data inserted in a file only changes the chunks around it, so new builds share more chunks with the previous ones.
+    Variable,
+}
+
+/// This type is used to create a new build from a directory: the files are split into chunks, the chunk files are written
+/// into a cloud directory with the same layout as Epic's CDN and the FManifest describing the build is returned.
+/// Chunks are named after the SHA1 of their data, so identical data is only stored once and building the same directory twice gives the same chunks.
+pub struct ManifestBuilder {
+    app_id: u32,
+    app_name: String,
+    build_version: String,
+    launch_exe: String,
+    launch_command: String,
+    feature_level: EFeatureLevel,
+    chunking_mode: EChunkingMode,
+    window_size: usize,
+    compress_chunks: bool,
+    custom_fields: IndexMap<String, String>,
+}
+
+impl ManifestBuilder {
+    pub fn new(app_name: impl Into<String>, build_version: impl Into<String>) -> ManifestBuilder {
+        ManifestBuilder {
+            app_id: 0,
+            app_name: app_name.into(),
+            build_version: build_version.into(),
+            launch_exe: String::new(),
+            launch_command: String::new(),
+            feature_level: EFeatureLevel::Latest,
+            chunking_mode: EChunkingMode::Fixed,
+            window_size: DEFAULT_CHUNK_WINDOW_SIZE,
+            compress_chunks: true,
+            custom_fields: IndexMap::new(),
+        }
+    }
+
+    pub fn with_app_id(mut self, app_id: u32) -> ManifestBuilder {
+        self.app_id = app_id;
+        self
+    }
+
+    pub fn with_launch_exe(mut self, launch_exe: impl Into<String>, launch_command: impl Into<String>) -> ManifestBuilder {
+        self.launch_exe = launch_exe.into();
+        self.launch_command = launch_command.into();
+        self
+    }
+
+    /// This function is used to set the feature level of the manifest, which also decides where the chunk files are written
+    pub fn with_feature_level(mut self, feature_level: EFeatureLevel) -> ManifestBuilder {
+        self.feature_level = feature_level;
+        self
+    }
+
+    /// This function is used to choose how files are split and the maximum size of a chunk
+    pub fn with_chunking(mut self, chunking_mode: EChunkingMode, window_size: usize) -> ManifestBuilder {
+        self.chunking_mode = chunking_mode;
+        self.window_size = window_size.max(VARIABLE_CHUNK_HASH_WINDOW * 4);
+        self
+    }
+
+    pub fn with_compressed_chunks(mut self, compress_chunks: bool) -> ManifestBuilder {
+        self.compress_chunks = compress_chunks;
+        self
+    }
+
+    pub fn with_custom_field(mut self, key: impl Into<String>, value: impl Into<String>) -> ManifestBuilder {
+        self.custom_fields.insert(key.into(), value.into());
+        self
+    }
+
+    /// This function is used to chunk every file of `source_dir`, write the chunk files into `cloud_dir` and create the manifest of the build.
+    /// The manifest itself is not written, use ManifestWriter to save it.
+    pub fn build(self, source_dir: impl AsRef<Path>, cloud_dir: impl AsRef<Path>) -> ParseResult<FManifest> {
+        let source_dir = source_dir.as_ref();
+        let mut chunker = Chunker::new(&self, cloud_dir.as_ref());
+        let mut entries = vec![];
+
+        for (filename, path) in list_files(source_dir)? {
+            let metadata = fs::symlink_metadata(&path)?;
+
+            let mut entry = FFileManifest {
+                filename,
+                flags: file_flags(&metadata).bits(),
+                ..Default::default()
+            };
+
+            if metadata.file_type().is_symlink() {
+                entry.syslink_target = fs::read_link(&path)?.to_string_lossy().replace('\\', "/");
+
+                // the installer refuses symlinks pointing outside of the install directory
+                if entry.symlink_target_filename().is_none() {
+                    return Err(ParseError::InvalidPath(entry.syslink_target));
+                }
+
+                entries.push(entry);
+                continue;
+            }
+
+            let file_index = entries.len();
+            let mut hasher = Sha1::new();
+            let mut file_size = 0u64;
+
+            let mut reader = BufReader::new(File::open(&path)?);
+            let mut buffer = vec![0u8; READ_BUFFER_SIZE];
+            loop {
+                let length = reader.read(&mut buffer)?;
+                if length == 0 {
+                    break;
+                }
+
+                hasher.update(&buffer[..length]);
+                chunker.push(file_index, &buffer[..length])?;
+                file_size += length as u64;
+            }
+
+            if self.chunking_mode == EChunkingMode::Variable {
+                chunker.flush()?;
+            }
+
+            entry.hash = FSHAHash::new(hasher.finalize().into());
+            entry.file_size = file_size;
+            entries.push(entry);
+        }
+
+        chunker.flush()?;
+
+        for (file_index, parts) in chunker.file_parts {
+            entries[file_index].chunk_parts = parts;
+        }
+
+        let manifest = self.manifest(chunker.chunks, entries);
+
+        // the header is filled by writing the manifest once
+        FManifestParser::new(&ManifestWriter::new(&manifest).write()?).parse()
+    }
+
+    fn manifest(&self, chunks: Vec<FChunkInfo>, entries: Vec<FFileManifest>) -> FManifest {
+        let mut meta = FManifestMeta {
+            data_version: 2,
+            feature_level: self.feature_level,
+            b_is_file_data: false,
+            app_id: self.app_id,
+            app_name: self.app_name.clone(),
+            build_version: self.build_version.clone(),
+            launch_exe: self.launch_exe.clone(),
+            launch_command: self.launch_command.clone(),
+            prerequisites: vec![],
+            prereq_name: String::new(),
+            prereq_path: String::new(),
+            prereq_args: String::new(),
+            build_id: None,
+            prereq_ids: vec![],
+            uninstall_action_path: Some(String::new()),
+            uninstall_action_args: Some(String::new()),
+        };
+        meta.build_id = Some(meta.backwards_compatible_build_id());
+
+        FManifest {
+            header: FManifestHeader {
+                magic: MANIFEST_MAGIC,
+                header_size: MANIFEST_HEADER_SIZE,
+                data_size_uncompressed: 0,
+                data_size_compressed: 0,
+                sha_hash: FSHAHash::default(),
+                stored_as: EManifestStorageFlags::Compressed,
+                version: self.feature_level,
+            },
+            meta,
+            chunk_list: FChunkList {
+                _manifest_version: self.feature_level,
+                _size: 0,
+                _version: 0,
+                chunks,
+            },
+            file_list: FFileManifestList {
+                _version: 0,
+                _size: 0,
+                _count: entries.len() as u32,
+                entries,
+            },
+            custom_fields: FCustomFields {
+                _size: 0,
+                _version: 0,
+                fields: self.custom_fields.clone(),
+            },
+        }
+    }
+}
+
+// a chunk part waiting for the chunk it belongs to to be written
+struct PendingPart {
+    file_index: usize,
+    offset: usize,
+    size: usize,
+    file_offset: usize,
+}
+
+struct Chunker<'a> {
+    cloud_dir: &'a Path,
+    feature_level: EFeatureLevel,
+    chunking_mode: EChunkingMode,
+    window_size: usize,
+    compress_chunks: bool,
+    rolling_hash: FRollingHash,
+    boundary_mask: u64,
+    data: Vec<u8>,
+    pending_parts: Vec<PendingPart>,
+    file_offsets: HashMap<usize, usize>,
+    chunks: Vec<FChunkInfo>,
+    known_chunks: HashMap<FGuid, usize>,
+    file_parts: HashMap<usize, Vec<FChunkPart>>,
+}
+
+impl<'a> Chunker<'a> {
+    fn new(builder: &ManifestBuilder, cloud_dir: &'a Path) -> Chunker<'a> {
+        Chunker {
+            cloud_dir,
+            feature_level: builder.feature_level,
+            chunking_mode: builder.chunking_mode,
+            window_size: builder.window_size,
+            compress_chunks: builder.compress_chunks,
+            rolling_hash: FRollingHash::new(VARIABLE_CHUNK_HASH_WINDOW as u32),
+            boundary_mask: (builder.window_size / 4).next_power_of_two() as u64 - 1,
+            data: Vec::with_capacity(builder.window_size),
+            pending_parts: vec![],
+            file_offsets: HashMap::new(),
+            chunks: vec![],
+            known_chunks: HashMap::new(),
+            file_parts: HashMap::new(),
+        }
+    }
+
+    fn min_chunk_size(&self) -> usize {
+        match self.chunking_mode {
+            EChunkingMode::Fixed => self.window_size,
+            EChunkingMode::Variable => self.window_size / 4,
+        }
+    }
+
+    fn push(&mut self, file_index: usize, mut data: &[u8]) -> ParseResult<()> {
+        while !data.is_empty() {
+            let length = self.next_boundary(data);
+            self.append(file_index, &data[..length]);
+            data = &data[length..];
+
+            if self.data.len() >= self.window_size || self.chunking_mode == EChunkingMode::Variable && self.is_boundary(self.data.len()) {
+                self.flush()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // amount of bytes of `data` that go into the current chunk before it may end
+    fn next_boundary(&mut self, data: &[u8]) -> usize {
+        let room = self.window_size - self.data.len();
+
+        if self.chunking_mode == EChunkingMode::Fixed {
+            return room.min(data.len());
+        }
+
+        for (index, byte) in data.iter().take(room).enumerate() {
+            if self.rolling_hash.num_data_needed() > 0 {
+                self.rolling_hash.consume_byte(*byte);
+            } else {
+                self.rolling_hash.roll_forward(*byte);
+            }
+
+            if self.is_boundary(self.data.len() + index + 1) {
+                return index + 1;
+            }
+        }
+
+        room.min(data.len())
+    }
+
+    // `chunk_size` is the size the current chunk would have if it ended at the last byte given to the rolling hash
+    fn is_boundary(&self, chunk_size: usize) -> bool {
+        chunk_size >= self.min_chunk_size()
+            && self.rolling_hash.num_data_needed() == 0
+            && self.rolling_hash.get_window_hash() & self.boundary_mask == self.boundary_mask
+    }
+
+    fn append(&mut self, file_index: usize, data: &[u8]) {
+        let file_offset = self.file_offsets.entry(file_index).or_default();
+
+        match self.pending_parts.last_mut() {
+            Some(part) if part.file_index == file_index => part.size += data.len(),
+            _ => self.pending_parts.push(PendingPart {
+                file_index,
+                offset: self.data.len(),
+                size: data.len(),
+                file_offset: *file_offset,
+            }),
+        }
+
+        *file_offset += data.len();
+        self.data.extend_from_slice(data);
+    }
+
+    fn flush(&mut self) -> ParseResult<()> {
+        self.rolling_hash.clear();
+
+        if self.data.is_empty() {
+            return Ok(());
+        }
+
+        let data = std::mem::replace(&mut self.data, Vec::with_capacity(self.window_size));
+        let sha_hash = FSHAHash::new_from_hashable(&data);
+        let guid = guid_from_hash(&sha_hash);
+
+        if !self.known_chunks.contains_key(&guid) {
+            let mut info = FChunkInfo {
+                guid,
+                hash: FRollingHash::get_hash_for_data_set(&data),
+                sha_hash,
+                group_num: data_group_number(&guid),
+                uncompressed_size: data.len() as u32,
+                compressed_size: 0,
+            };
+
+            let chunk_file = FChunk::new(guid, data).to_bytes(self.compress_chunks)?;
+            info.compressed_size = chunk_file.len() as i64;
+
+            let path = self.cloud_dir.join(chunk_path(&info, self.feature_level));
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, chunk_file)?;
+
+            self.known_chunks.insert(guid, self.chunks.len());
+            self.chunks.push(info);
+        }
+
+        for part in self.pending_parts.drain(..) {
+            self.file_parts.entry(part.file_index).or_default().push(FChunkPart {
+                size: part.size as u32,
+                guid,
+                offset: part.offset as u32,
+                file_offset: part.file_offset,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+fn guid_from_hash(hash: &FSHAHash) -> FGuid {
+    let component = |index: usize| u32::from_le_bytes([hash.data[index * 4], hash.data[index * 4 + 1], hash.data[index * 4 + 2], hash.data[index * 4 + 3]]);
+
+    FGuid {
+        a: component(0),
+        b: component(1),
+        c: component(2),
+        d: component(3),
+    }
+}
+
+// every file and symbolic link of the directory, sorted by their manifest filename
+fn list_files(source_dir: &Path) -> ParseResult<Vec<(String, PathBuf)>> {
+    let mut files = vec![];
+    let mut directories = vec![(source_dir.to_path_buf(), String::new())];
+
+    while let Some((directory, prefix)) = directories.pop() {
+        for entry in fs::read_dir(&directory)? {
+            let entry = entry?;
+            let name = prefix.clone() + &entry.file_name().to_string_lossy();
+
+            if entry.file_type()?.is_dir() {
+                directories.push((entry.path(), name + "/"));
+            } else {
+                files.push((name, entry.path()));
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(unix)]
+fn file_flags(metadata: &fs::Metadata) -> EFileMetaFlags {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut flags = EFileMetaFlags::empty();
+    flags.set(EFileMetaFlags::ReadOnly, metadata.permissions().readonly());
+    flags.set(EFileMetaFlags::UnixExecutable, !metadata.file_type().is_symlink() && metadata.permissions().mode() & 0o111 != 0);
+    flags
+}
+
+#[cfg(not(unix))]
+fn file_flags(metadata: &fs::Metadata) -> EFileMetaFlags {
+    let mut flags = EFileMetaFlags::empty();
+    flags.set(EFileMetaFlags::ReadOnly, metadata.permissions().readonly());
+    flags
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::test_utils::{random_data, TempDir};
+
+    use super::*;
+
+    fn chunk_guids(builder: &ManifestBuilder, data: &[u8], read_size: usize) -> Vec<FGuid> {
+        let cloud_dir = TempDir::new("chunker");
+        let mut chunker = Chunker::new(builder, cloud_dir.path());
+
+        for piece in data.chunks(read_size) {
+            chunker.push(0, piece).unwrap();
+        }
+        chunker.flush().unwrap();
+
+        chunker.chunks.iter().map(|chunk| *chunk.guid()).collect()
+    }
+
+    #[test]
+    fn variable_chunks_dont_depend_on_read_size() {
+        let builder = ManifestBuilder::new("Test", "1").with_chunking(EChunkingMode::Variable, 64 * 1024);
+        let data = random_data(1024 * 1024, 1);
+
+        let expected = chunk_guids(&builder, &data, READ_BUFFER_SIZE);
+        assert!(expected.len() > 4);
+        assert_eq!(chunk_guids(&builder, &data, 1000), expected);
+        assert_eq!(chunk_guids(&builder, &data, 7), expected);
+    }
+
+    #[test]
+    fn variable_chunks_resync_after_insertion() {
+        let old_source = TempDir::new("old_source");
+        let new_source = TempDir::new("new_source");
+        let cloud_dir = TempDir::new("cloud");
+
+        let data = random_data(3 * 1024 * 1024, 2);
+        let mut new_data = data.clone();
+        new_data.splice(1_500_000..1_500_000, random_data(100, 3));
+
+        old_source.write("data.bin", &data);
+        new_source.write("data.bin", &new_data);
+
+        let builder = || ManifestBuilder::new("Test", "1").with_chunking(EChunkingMode::Variable, 64 * 1024);
+        let old_manifest = builder().build(old_source.path(), cloud_dir.path()).unwrap();
+        let new_manifest = builder().build(new_source.path(), cloud_dir.path()).unwrap();
+
+        let old_chunks: HashSet<&FGuid> = old_manifest.chunk_list.chunks().iter().map(|chunk| chunk.guid()).collect();
+        let new_chunks = new_manifest.chunk_list.chunks();
+        let shared_chunks = new_chunks.iter().filter(|chunk| old_chunks.contains(chunk.guid())).count();
+
+        // only the chunks around the insertion change
+        assert!(new_chunks.len() - shared_chunks <= 3, "{} of {} chunks changed", new_chunks.len() - shared_chunks, new_chunks.len());
+        assert_eq!(new_manifest.file_list.entries()[0].file_size(), new_data.len() as u64);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_outside_of_the_build_are_rejected() {
+        let source = TempDir::new("symlink_source");
+        let cloud_dir = TempDir::new("symlink_cloud");
+
+        source.write("lib/libfoo.so.1", b"foo");
+        std::os::unix::fs::symlink("libfoo.so.1", source.join("lib/libfoo.so")).unwrap();
+        let manifest = ManifestBuilder::new("Test", "1").build(source.path(), cloud_dir.path()).unwrap();
+        assert!(manifest.file_list.entries().iter().any(|file| file.syslink_target() == "libfoo.so.1"));
+
+        std::os::unix::fs::symlink("../../etc/passwd", source.join("lib/passwd")).unwrap();
+        assert!(matches!(ManifestBuilder::new("Test", "1").build(source.path(), cloud_dir.path()), Err(ParseError::InvalidPath(_))));
+    }
+}
@@ -2,10 +2,50 @@ use core::fmt;
 
 use crate::helper;
 
-use super::shared::{FGuid, FSHAHash};
+use super::shared::{EFeatureLevel, FGuid, FSHAHash};
 
+/// This function is used to get the directory chunks are stored in on the CDN for a given feature level
+pub fn chunk_data_dir(feature_level: EFeatureLevel) -> &'static str {
+    let version = feature_level.to_i32();
 
-#[derive(Default, Clone, serde::Serialize)]
+    if version < EFeatureLevel::DataFileRenames.to_i32() {
+        "Chunks"
+    } else if version < EFeatureLevel::ChunkCompressionSupport.to_i32() {
+        "ChunksV2"
+    } else if version < EFeatureLevel::VariableSizeChunksWithoutWindowSizeChunkInfo.to_i32() {
+        "ChunksV3"
+    } else {
+        "ChunksV4"
+    }
+}
+
+/// This function is used to get the path of a chunk relative to the CloudDir of a build, e.g. `ChunksV4/12/9F3A0C1D22E47B08_0123456789ABCDEF0123456789ABCDEF.chunk`
+pub fn chunk_path(chunk: &FChunkInfo, feature_level: EFeatureLevel) -> String {
+    if feature_level.to_i32() < EFeatureLevel::DataFileRenames.to_i32() {
+        format!("{}/{}/{}.chunk", chunk_data_dir(feature_level), chunk.group_num_str(), chunk.guid.to_string())
+    } else {
+        format!("{}/{}/{}_{}.chunk", chunk_data_dir(feature_level), chunk.group_num_str(), chunk.hash_str(), chunk.guid.to_string())
+    }
+}
+
+/// This function is used to get the URL of a chunk from the base URL of a build (its CloudDir)
+pub fn chunk_url(base_url: &str, chunk: &FChunkInfo, feature_level: EFeatureLevel) -> String {
+    format!("{}/{}", base_url.trim_end_matches('/'), chunk_path(chunk, feature_level))
+}
+
+/// This function is used to compute the data group of a chunk, for manifests too old to store it
+pub fn data_group_number(guid: &FGuid) -> u8 {
+    let mut crc = flate2::Crc::new();
+
+    for component in [guid.a, guid.b, guid.c, guid.d] {
+        crc.update(&component.to_le_bytes());
+    }
+
+    (crc.sum() % 100) as u8
+}
+
+
+#[derive(Default, Clone, serde::Serialize, serde::Deserialize)]
 pub struct FChunkInfo {
     pub(crate) guid: FGuid,
     pub(crate) hash: u64,
@@ -82,4 +122,43 @@ impl FChunkInfo {
     pub fn compressed_size(&self) -> i64 {
         self.compressed_size
     }
-}
\ No newline at end of file
+
+    /// See `chunk_path`
+    pub fn path(&self, feature_level: EFeatureLevel) -> String {
+        chunk_path(self, feature_level)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk() -> FChunkInfo {
+        let guid = FGuid { a: 0x01234567, b: 0x89ABCDEF, c: 0xDEADBEEF, d: 0x00000042 };
+
+        FChunkInfo {
+            guid,
+            hash: 0x0000A1B2C3D4E5F6,
+            group_num: data_group_number(&guid),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn data_group_is_crc32_of_the_guid() {
+        assert_eq!(chunk().group_num, 77);
+    }
+
+    #[test]
+    fn chunk_paths_follow_the_cdn_layout() {
+        let chunk = chunk();
+
+        assert_eq!(chunk_path(&chunk, EFeatureLevel::Original), "Chunks/77/0123456789ABCDEFDEADBEEF00000042.chunk");
+        assert_eq!(chunk_path(&chunk, EFeatureLevel::DataFileRenames), "ChunksV2/77/0000A1B2C3D4E5F6_0123456789ABCDEFDEADBEEF00000042.chunk");
+        assert_eq!(chunk_path(&chunk, EFeatureLevel::ChunkCompressionSupport), "ChunksV3/77/0000A1B2C3D4E5F6_0123456789ABCDEFDEADBEEF00000042.chunk");
+        assert_eq!(chunk_path(&chunk, EFeatureLevel::Latest), "ChunksV4/77/0000A1B2C3D4E5F6_0123456789ABCDEFDEADBEEF00000042.chunk");
+        assert_eq!(
+            chunk_url("https://cdn.example.com/Builds/Org/CloudDir/", &chunk, EFeatureLevel::Latest),
+            "https://cdn.example.com/Builds/Org/CloudDir/ChunksV4/77/0000A1B2C3D4E5F6_0123456789ABCDEFDEADBEEF00000042.chunk"
+        );
+    }
+}
@@ -1,14 +1,14 @@
-use crate::{error::ParseError, manifest::shared::FGuid, reader::ByteReader, ParseResult};
+use crate::{manifest::shared::FGuid, reader::ByteReader, writer::ByteWriter, ParseResult};
 
 use super::{chunk_info::FChunkInfo, shared::EFeatureLevel};
 
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct FChunkList {
-    _manifest_version:EFeatureLevel,
-    _size: u32,
-    _version: u8,
-    chunks: Vec<FChunkInfo>
+    pub(crate) _manifest_version:EFeatureLevel,
+    pub(crate) _size: u32,
+    pub(crate) _version: u8,
+    pub(crate) chunks: Vec<FChunkInfo>
 }
 
 impl FChunkList {
@@ -16,11 +16,12 @@ impl FChunkList {
     pub fn parse(reader: &mut ByteReader, manifest_version:EFeatureLevel) -> ParseResult<FChunkList> {
         let reader_start = reader.tell();
 
-        let size = reader.read()?;
-        let version = reader.read()?;
-        let count:u32 = reader.read()?;
+        let size:u32 = reader.read()?;
+        let version:u8 = reader.read()?;
+        // every chunk starts with its guid
+        let count = reader.read_count(std::mem::size_of::<FGuid>())?;
 
-        let mut chunks:Vec<FChunkInfo> = vec![Default::default(); count as usize];
+        let mut chunks:Vec<FChunkInfo> = vec![Default::default(); count];
 
         for chunk in chunks.iter_mut() {
             chunk.guid = reader.read()?;
@@ -46,10 +47,7 @@ impl FChunkList {
             chunk.compressed_size = reader.read()?;
         }
 
-        if reader_start + size as usize != reader.tell() {
-            println!("Chunk header size mismatch: expected {} but got {}\nChunkHeader version : {}", size, reader.tell() - reader_start, version);
-            return Err(ParseError::InvalidData);
-        }
+        reader.end_section("FChunkList", reader_start, size as usize, Some(version as u32))?;
 
         Ok(FChunkList {
             _manifest_version: manifest_version,
@@ -59,6 +57,41 @@ impl FChunkList {
         })
     }
 
+    /// This function is used to serialize the FChunkInfos into a ByteWriter
+    pub fn write(&self, writer: &mut ByteWriter) {
+        let start = writer.tell();
+
+        writer.write(&0u32); //size, filled once everything is written
+        writer.write(&self._version);
+        writer.write(&(self.chunks.len() as u32));
+
+        for chunk in self.chunks.iter() {
+            writer.write(&chunk.guid);
+        }
+
+        for chunk in self.chunks.iter() {
+            writer.write(&chunk.hash);
+        }
+
+        for chunk in self.chunks.iter() {
+            writer.write(&chunk.sha_hash);
+        }
+
+        for chunk in self.chunks.iter() {
+            writer.write(&chunk.group_num);
+        }
+
+        for chunk in self.chunks.iter() {
+            writer.write(&chunk.uncompressed_size);
+        }
+
+        for chunk in self.chunks.iter() {
+            writer.write(&chunk.compressed_size);
+        }
+
+        writer.write_u32_at(start, (writer.tell() - start) as u32);
+    }
+
     pub fn find_by_guid(&self, guid:&FGuid) -> Option<&FChunkInfo> {
         self.chunks.iter().find(|chunk| chunk.guid() == guid)
     }
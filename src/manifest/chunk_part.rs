@@ -1,14 +1,17 @@
-use crate::{error::ParseError, reader::ByteReader, ParseResult};
+use crate::{reader::ByteReader, writer::ByteWriter, ParseResult};
 
 use super::shared::FGuid;
 
+/// Size of a serialized FChunkPart, including the size field itself
+pub const CHUNK_PART_SIZE:u32 = 28;
 
-#[derive(Debug, Default, Clone, serde::Serialize)]
+
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
 pub struct FChunkPart {
-    size:u32,
-    guid: FGuid,
-    offset: u32,
-    file_offset: usize,
+    pub(crate) size:u32,
+    pub(crate) guid: FGuid,
+    pub(crate) offset: u32,
+    pub(crate) file_offset: usize,
 }
 
 impl FChunkPart {
@@ -22,10 +25,7 @@ impl FChunkPart {
         let offset = reader.read()?;
         let size = reader.read()?;
 
-        if start + struct_size as usize != reader.tell() {
-            println!("ChunkPart size mismatch: expected {} but got {}", struct_size, reader.tell() - start);
-            return Err(ParseError::SizeMismatch);
-        }
+        reader.end_section("FChunkPart", start, struct_size as usize, None)?;
 
         Ok(FChunkPart {
             size,
@@ -35,6 +35,14 @@ impl FChunkPart {
         })
     }
 
+    /// This function is used to serialize a FChunkPart into a ByteWriter
+    pub fn write(&self, writer:&mut ByteWriter) {
+        writer.write(&CHUNK_PART_SIZE);
+        writer.write(&self.guid);
+        writer.write(&self.offset);
+        writer.write(&self.size);
+    }
+
     pub fn file_offset(&self) -> usize {
         self.file_offset
     }
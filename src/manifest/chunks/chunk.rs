@@ -0,0 +1,172 @@
+use std::io::Write;
+
+use flate2::{write::ZlibEncoder, Compression};
+
+use crate::{crypto::Decryptor, error::ParseError, manifest::shared::{EChunkHashFlags, EChunkStorageFlags, FGuid, FSHAHash}, reader::ByteReader, rolling_hash::FRollingHash, writer::ByteWriter, ParseResult};
+
+use super::chunk_header::FChunkHeader;
+
+/// This type represents a whole chunk file: its header and its decompressed data
+#[derive(Debug, Clone)]
+pub struct FChunk {
+    header: FChunkHeader,
+    data: Vec<u8>,
+}
+
+impl FChunk {
+    /// This function is used to parse a chunk file from a ByteReader.
+    /// The data is decompressed and verified against the hashes stored in the header.
+    pub fn parse(reader: &mut ByteReader) -> ParseResult<FChunk> {
+        FChunk::parse_with_decryptor(reader, None)
+    }
+
+    /// This function is used to parse and verify a chunk file that may be encrypted, see `parse`
+    pub fn parse_with_decryptor(reader: &mut ByteReader, decryptor: Option<&dyn Decryptor>) -> ParseResult<FChunk> {
+        let header = FChunkHeader::parse(reader)?;
+        let data = header.get_data_with_decryptor(reader, decryptor)?;
+
+        let chunk = FChunk {
+            header,
+            data
+        };
+        chunk.verify()?;
+
+        Ok(chunk)
+    }
+
+    /// This function is used to create a chunk from its data, its hashes are computed from the data
+    pub fn new(guid: FGuid, data: Vec<u8>) -> FChunk {
+        let header = FChunkHeader::new(
+            guid,
+            FRollingHash::get_hash_for_data_set(&data),
+            FSHAHash::new_from_hashable(&data),
+            EChunkStorageFlags::empty(),
+            data.len() as u32,
+            data.len() as u32
+        );
+
+        FChunk {
+            header,
+            data
+        }
+    }
+
+    /// This function is used to serialize the chunk into a chunk file.
+    /// When `compress` is set the data is stored compressed, unless compressing it doesn't make it smaller.
+    pub fn to_bytes(&self, compress: bool) -> ParseResult<Vec<u8>> {
+        let data_size = u32::try_from(self.data.len()).map_err(|_| ParseError::Overflow)?;
+
+        let compressed_data = if compress {
+            let mut encoder = ZlibEncoder::new(Vec::with_capacity(self.data.len()), Compression::default());
+            encoder.write_all(&self.data).map_err(|_| ParseError::CompressionError)?;
+            Some(encoder.finish().map_err(|_| ParseError::CompressionError)?).filter(|compressed_data| compressed_data.len() < self.data.len())
+        } else {
+            None
+        };
+
+        let (stored_as, stored_data) = match &compressed_data {
+            Some(compressed_data) => (EChunkStorageFlags::Compressed, compressed_data.as_slice()),
+            None => (EChunkStorageFlags::empty(), self.data.as_slice()),
+        };
+
+        let header = FChunkHeader::new(
+            self.header.guid(),
+            FRollingHash::get_hash_for_data_set(&self.data),
+            FSHAHash::new_from_hashable(&self.data),
+            stored_as,
+            stored_data.len() as u32,
+            data_size
+        );
+
+        let mut writer = ByteWriter::new();
+        header.write(&mut writer);
+        writer.write_bytes(stored_data);
+
+        Ok(writer.into_inner())
+    }
+
+    /// This function is used to parse a chunk file without verifying its data
+    pub fn parse_unverified(reader: &mut ByteReader) -> ParseResult<FChunk> {
+        let header = FChunkHeader::parse(reader)?;
+        let data = header.get_data(reader)?;
+
+        Ok(FChunk {
+            header,
+            data
+        })
+    }
+
+    /// This function is used to parse a chunk file from a slice, see `parse`
+    pub fn from_bytes(data: &[u8]) -> ParseResult<FChunk> {
+        FChunk::parse(&mut ByteReader::from_slice(data))
+    }
+
+    /// This function is used to check the data against the hashes stored in the header, according to its hash type.
+    /// Chunks older than `StoresShaAndHashType` don't store a hash type and only have a rolling hash.
+    pub fn verify(&self) -> ParseResult<()> {
+        let hash_type = self.header.hash_type().unwrap_or(EChunkHashFlags::RollingPoly64);
+
+        if matches!(hash_type, EChunkHashFlags::RollingPoly64 | EChunkHashFlags::Both)
+            && FRollingHash::get_hash_for_data_set(&self.data) != self.header.rolling_hash() {
+            return Err(ParseError::HashMismatch);
+        }
+
+        if matches!(hash_type, EChunkHashFlags::Sha1 | EChunkHashFlags::Both) {
+            let sha_hash = self.header.sha_hash().ok_or(ParseError::InvalidData)?;
+
+            if FSHAHash::new_from_hashable(&self.data) != sha_hash {
+                return Err(ParseError::HashMismatch);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn header(&self) -> &FChunkHeader {
+        &self.header
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::random_data;
+
+    use super::*;
+
+    const GUID: FGuid = FGuid { a: 1, b: 2, c: 3, d: 4 };
+
+    #[test]
+    fn chunk_files_are_read_back() {
+        for (data, compress) in [(random_data(1000, 11), false), (random_data(100, 12).repeat(10), true)] {
+            let chunk_file = FChunk::new(GUID, data.clone()).to_bytes(compress).unwrap();
+            let chunk = FChunk::from_bytes(&chunk_file).unwrap();
+
+            assert_eq!(chunk.header().guid(), GUID);
+            assert_eq!(chunk.header().is_compressed(), compress);
+            assert_eq!(chunk.header().data_size_uncompressed(), Some(1000));
+            assert_eq!(chunk.header().rolling_hash(), FRollingHash::get_hash_for_data_set(&data));
+            assert_eq!(chunk.header().sha_hash(), Some(FSHAHash::new_from_hashable(&data)));
+            assert_eq!(chunk.data(), data);
+        }
+    }
+
+    #[test]
+    fn corrupted_chunks_are_only_read_unverified() {
+        let mut chunk_file = FChunk::new(GUID, random_data(1000, 13)).to_bytes(false).unwrap();
+        *chunk_file.last_mut().unwrap() ^= 0xFF;
+
+        assert!(matches!(FChunk::from_bytes(&chunk_file), Err(ParseError::HashMismatch)));
+
+        let chunk = FChunk::parse_unverified(&mut ByteReader::from_slice(&chunk_file)).unwrap();
+        assert_eq!(chunk.data(), &chunk_file[chunk_file.len() - 1000..]);
+        assert!(matches!(chunk.verify(), Err(ParseError::HashMismatch)));
+    }
+}
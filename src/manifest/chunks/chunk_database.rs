@@ -0,0 +1,353 @@
+use std::{collections::HashMap, fs::File, io::{BufReader, ErrorKind, Read, Seek, SeekFrom, Write}, path::Path, sync::{Arc, Mutex}};
+
+use crate::{crypto::Decryptor, error::ParseError, manifest::shared::FGuid, reader::ByteReader, writer::ByteWriter, ParseResult};
+
+use super::{chunk::FChunk, chunk_header::FChunkHeader};
+
+pub const CHUNK_DATABASE_MAGIC: u32 = 0xB1FE3AA3;
+
+/// Size of the magic, version and header size fields, which are enough to know the size of the whole header
+const CHUNK_DATABASE_HEADER_PREFIX_SIZE: usize = 12;
+/// Size of a serialized FChunkLocation
+const CHUNK_LOCATION_SIZE: usize = 28;
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum EChunkDatabaseVersion {
+    Invalid,
+    Original,
+    // Stores the size of the data following the header.
+    StoresDataSize,
+
+    // Always after the latest version, signifies the latest version plus 1 to allow initialization simplicity.
+    LatestPlusOne,
+    Latest
+}
+
+impl EChunkDatabaseVersion {
+    pub fn to_u32(&self) -> u32 {
+        match self {
+            EChunkDatabaseVersion::Invalid => 0,
+            EChunkDatabaseVersion::Original => 1,
+            EChunkDatabaseVersion::StoresDataSize => 2,
+            EChunkDatabaseVersion::LatestPlusOne => 3,
+            EChunkDatabaseVersion::Latest => EChunkDatabaseVersion::LatestPlusOne.to_u32() - 1,
+        }
+    }
+
+    pub fn from_u32(value: u32) -> EChunkDatabaseVersion {
+        match value {
+            1 => EChunkDatabaseVersion::Original,
+            2 => EChunkDatabaseVersion::StoresDataSize,
+            3 => EChunkDatabaseVersion::LatestPlusOne,
+            _ => EChunkDatabaseVersion::Invalid
+        }
+    }
+}
+
+/// This type represents an entry of the table of contents of a chunk database: where a chunk file is stored in the database
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct FChunkLocation {
+    chunk_id: FGuid,
+    byte_start: u64,
+    byte_size: u32,
+}
+
+impl FChunkLocation {
+    pub fn parse(reader: &mut ByteReader) -> ParseResult<FChunkLocation> {
+        Ok(FChunkLocation {
+            chunk_id: reader.read()?,
+            byte_start: reader.read()?,
+            byte_size: reader.read()?,
+        })
+    }
+
+    pub fn write(&self, writer: &mut ByteWriter) {
+        writer.write(&self.chunk_id);
+        writer.write(&self.byte_start);
+        writer.write(&self.byte_size);
+    }
+
+    pub fn chunk_id(&self) -> &FGuid {
+        &self.chunk_id
+    }
+
+    /// Offset of the chunk file from the beginning of the chunk database
+    pub fn byte_start(&self) -> u64 {
+        self.byte_start
+    }
+
+    pub fn byte_size(&self) -> u32 {
+        self.byte_size
+    }
+}
+
+/// This type is the header of a chunk database (.chunkdb), it is followed by the chunk files it contains
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct FChunkDatabaseHeader {
+    version: EChunkDatabaseVersion,
+    header_size: u32,
+    data_size: Option<u64>,
+    contents: Vec<FChunkLocation>,
+}
+
+impl FChunkDatabaseHeader {
+    /// This function is used to parse the header, the reader must be positioned at the beginning of the chunk database
+    pub fn parse(reader: &mut ByteReader) -> ParseResult<FChunkDatabaseHeader> {
+        let start = reader.tell();
+        let magic = reader.read::<u32>()?;
+
+        if magic != CHUNK_DATABASE_MAGIC {
+            return Err(ParseError::InvalidMagic)
+        }
+
+        let raw_version = reader.read::<u32>()?;
+        let version = EChunkDatabaseVersion::from_u32(raw_version);
+        let header_size = reader.read::<u32>()?;
+
+        let data_size = if raw_version >= EChunkDatabaseVersion::StoresDataSize.to_u32() {
+            Some(reader.read()?)
+        } else {
+            None
+        };
+
+        let contents = reader.read_array(FChunkLocation::parse)?;
+
+        if reader.tell() - start != header_size as usize {
+            return Err(ParseError::SectionSizeMismatch {
+                section: "FChunkDatabaseHeader",
+                offset: start,
+                expected: header_size as usize,
+                actual: reader.tell() - start,
+                data_version: Some(raw_version)
+            })
+        }
+
+        Ok(FChunkDatabaseHeader {
+            version,
+            header_size,
+            data_size,
+            contents,
+        })
+    }
+
+    /// This function is used to serialize the header, sizes are expected to be up to date
+    pub fn write(&self, writer: &mut ByteWriter) {
+        writer.write(&CHUNK_DATABASE_MAGIC);
+        writer.write(&self.version.to_u32());
+        writer.write(&self.header_size);
+
+        if let Some(data_size) = self.data_size {
+            writer.write(&data_size);
+        }
+
+        writer.write_array(&self.contents, |writer, location| location.write(writer));
+    }
+
+    pub fn version(&self) -> EChunkDatabaseVersion {
+        self.version
+    }
+
+    pub fn header_size(&self) -> u32 {
+        self.header_size
+    }
+
+    /// Size of the chunk files following the header, only stored from `StoresDataSize`
+    pub fn data_size(&self) -> Option<u64> {
+        self.data_size
+    }
+
+    /// The table of contents of the chunk database
+    pub fn contents(&self) -> &[FChunkLocation] {
+        &self.contents
+    }
+}
+
+/// This type is used to read the chunks embedded in a chunk database.
+/// Only the header is read when the database is opened, chunks are read on demand.
+pub struct ChunkDatabase<R: Read + Seek> {
+    source: Mutex<R>,
+    header: FChunkDatabaseHeader,
+    locations: HashMap<FGuid, usize>,
+    decryptor: Option<Arc<dyn Decryptor>>,
+}
+
+impl ChunkDatabase<BufReader<File>> {
+    pub fn open_file(path: impl AsRef<Path>) -> ParseResult<ChunkDatabase<BufReader<File>>> {
+        ChunkDatabase::open(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read + Seek> ChunkDatabase<R> {
+    /// This function is used to read the header of a chunk database, `source` must be positioned at its beginning
+    pub fn open(mut source: R) -> ParseResult<ChunkDatabase<R>> {
+        let mut header_data = vec![0u8; CHUNK_DATABASE_HEADER_PREFIX_SIZE];
+        source.read_exact(&mut header_data)?;
+
+        let header_size = u32::from_le_bytes(header_data[8..12].try_into().map_err(|_| ParseError::InvalidData)?) as usize;
+        if header_size < CHUNK_DATABASE_HEADER_PREFIX_SIZE {
+            return Err(ParseError::InvalidData);
+        }
+
+        // the header size can't be trusted, only the bytes actually in the source are allocated
+        source.by_ref().take((header_size - CHUNK_DATABASE_HEADER_PREFIX_SIZE) as u64).read_to_end(&mut header_data)?;
+        if header_data.len() != header_size {
+            return Err(ParseError::IoError(ErrorKind::UnexpectedEof));
+        }
+
+        let header = FChunkDatabaseHeader::parse(&mut ByteReader::new(header_data))?;
+        let locations = header.contents.iter().enumerate().map(|(index, location)| (location.chunk_id, index)).collect();
+
+        Ok(ChunkDatabase {
+            source: Mutex::new(source),
+            header,
+            locations,
+            decryptor: None,
+        })
+    }
+
+    /// This function is used to set the decryptor used to read encrypted chunks
+    pub fn set_decryptor(&mut self, decryptor: impl Decryptor + 'static) {
+        self.decryptor = Some(Arc::new(decryptor));
+    }
+
+    pub fn header(&self) -> &FChunkDatabaseHeader {
+        &self.header
+    }
+
+    pub fn contains(&self, guid: &FGuid) -> bool {
+        self.locations.contains_key(guid)
+    }
+
+    pub fn location(&self, guid: &FGuid) -> Option<&FChunkLocation> {
+        self.locations.get(guid).map(|index| &self.header.contents[*index])
+    }
+
+    /// This function is used to read the raw chunk file of a chunk, as it would be stored on the CDN
+    pub fn read_chunk_file(&self, guid: &FGuid) -> ParseResult<Vec<u8>> {
+        let location = self.location(guid).ok_or(ParseError::MissingChunk(*guid))?;
+        let (byte_start, byte_size) = (location.byte_start, location.byte_size as usize);
+
+        let mut data = vec![0u8; byte_size];
+        let mut source = self.source.lock().map_err(|_| ParseError::InvalidData)?;
+        source.seek(SeekFrom::Start(byte_start))?;
+        source.read_exact(&mut data)?;
+
+        Ok(data)
+    }
+
+    /// This function is used to read, decompress and verify a chunk
+    pub fn read_chunk(&self, guid: &FGuid) -> ParseResult<FChunk> {
+        let data = self.read_chunk_file(guid)?;
+        let chunk = FChunk::parse_with_decryptor(&mut ByteReader::from_slice(&data), self.decryptor.as_deref())?;
+
+        if chunk.header().guid() != *guid {
+            return Err(ParseError::InvalidData);
+        }
+
+        Ok(chunk)
+    }
+}
+
+/// This type is used to bundle chunk files into a chunk database
+#[derive(Debug, Default)]
+pub struct ChunkDatabaseWriter {
+    chunks: Vec<(FGuid, Vec<u8>)>,
+}
+
+impl ChunkDatabaseWriter {
+    pub fn new() -> ChunkDatabaseWriter {
+        ChunkDatabaseWriter::default()
+    }
+
+    /// This function is used to add a chunk file, its GUID is read from its header
+    pub fn add_chunk_file(&mut self, data: Vec<u8>) -> ParseResult<FGuid> {
+        let guid = FChunkHeader::parse(&mut ByteReader::from_slice(&data))?.guid();
+        self.chunks.push((guid, data));
+
+        Ok(guid)
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    /// This function is used to write the header followed by every chunk file, in the order they were added
+    pub fn write(&self, mut output: impl Write) -> ParseResult<()> {
+        let header_size = CHUNK_DATABASE_HEADER_PREFIX_SIZE + 8 + 4 + self.chunks.len() * CHUNK_LOCATION_SIZE;
+
+        let mut byte_start = header_size as u64;
+        let mut contents = Vec::with_capacity(self.chunks.len());
+        for (guid, data) in self.chunks.iter() {
+            contents.push(FChunkLocation {
+                chunk_id: *guid,
+                byte_start,
+                byte_size: u32::try_from(data.len()).map_err(|_| ParseError::Overflow)?,
+            });
+            byte_start += data.len() as u64;
+        }
+
+        let header = FChunkDatabaseHeader {
+            version: EChunkDatabaseVersion::Latest,
+            header_size: u32::try_from(header_size).map_err(|_| ParseError::Overflow)?,
+            data_size: Some(byte_start - header_size as u64),
+            contents,
+        };
+
+        let mut writer = ByteWriter::new();
+        header.write(&mut writer);
+        output.write_all(&writer.into_inner())?;
+
+        for (_, data) in self.chunks.iter() {
+            output.write_all(data)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn chunk_file(a: u32, data: &[u8], compress: bool) -> Vec<u8> {
+        FChunk::new(FGuid { a, b: 2, c: 3, d: 4 }, data.to_vec()).to_bytes(compress).unwrap()
+    }
+
+    #[test]
+    fn written_database_is_read_back() {
+        let chunk_files = [chunk_file(1, &[1; 1000], true), chunk_file(2, &[2, 3, 4], false)];
+
+        let mut writer = ChunkDatabaseWriter::new();
+        for data in chunk_files.iter() {
+            writer.add_chunk_file(data.clone()).unwrap();
+        }
+
+        let mut output = vec![];
+        writer.write(&mut output).unwrap();
+
+        let database = ChunkDatabase::open(Cursor::new(output)).unwrap();
+        assert_eq!(database.header().contents().len(), 2);
+
+        let guid = FGuid { a: 1, b: 2, c: 3, d: 4 };
+        assert_eq!(database.read_chunk_file(&guid).unwrap(), chunk_files[0]);
+        assert_eq!(database.read_chunk(&guid).unwrap().data(), &[1; 1000]);
+        assert!(matches!(database.read_chunk(&FGuid::default()), Err(ParseError::MissingChunk(_))));
+    }
+
+    #[test]
+    fn header_larger_than_the_database_is_rejected() {
+        let mut writer = ByteWriter::new();
+        writer.write(&CHUNK_DATABASE_MAGIC);
+        writer.write(&EChunkDatabaseVersion::Latest.to_u32());
+        writer.write(&u32::MAX);
+        writer.write(&0u64);
+
+        assert!(matches!(ChunkDatabase::open(Cursor::new(writer.into_inner())), Err(ParseError::IoError(ErrorKind::UnexpectedEof))));
+    }
+}
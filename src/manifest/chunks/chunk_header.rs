@@ -1,10 +1,12 @@
 use std::io::Read;
 
-use crate::{manifest::shared::{EChunkHashFlags, EChunkStorageFlags, EChunkVersion, FGuid, FSHAHash}, reader::ByteReader, ParseResult};
+use crate::{crypto::Decryptor, error::ParseError, manifest::shared::{EChunkHashFlags, EChunkStorageFlags, EChunkVersion, FGuid, FSHAHash}, reader::ByteReader, writer::ByteWriter, ParseResult};
 
 pub const CHUNK_MAGIC: u32 = 0xB1FE3AA2;
+/// Size of the header of the latest chunk version
+pub const CHUNK_HEADER_SIZE: u32 = 66;
 
-#[derive(Debug, Default, Clone, serde::Serialize)]
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
 pub struct FChunkHeader {
     magic: u32,
     version: EChunkVersion,
@@ -31,7 +33,7 @@ impl FChunkHeader {
         let data_size_compressed = reader.read()?;
         let guid: FGuid = reader.read()?;
         let rolling_hash = reader.read()?;
-        let stored_as = EChunkStorageFlags::from(reader.read::<u8>()?);
+        let stored_as = EChunkStorageFlags::try_from(reader.read::<u8>()?)?;
 
         let mut chunk_header = FChunkHeader {
             magic,
@@ -48,9 +50,7 @@ impl FChunkHeader {
         {
             chunk_header.sha_hash = reader.read::<FSHAHash>().ok();
 
-            if let Some(hash_type) = reader.read::<u8>().ok() {
-                chunk_header.hash_type = Some(EChunkHashFlags::from(hash_type));
-            }
+            chunk_header.hash_type = Some(EChunkHashFlags::try_from(reader.read::<u8>()?)?);
         }
 
         if version.to_i32() >= EChunkVersion::StoresDataSizeUncompressed.to_i32() 
@@ -59,13 +59,54 @@ impl FChunkHeader {
         }
 
         if reader.tell() - start != chunk_header.header_size as usize {
-            println!("{} bytes are missing/were not deserialized.", chunk_header.header_size - (reader.tell() - start) as u32);
-            return Err(crate::error::ParseError::SizeMismatch)
+            return Err(crate::error::ParseError::SectionSizeMismatch {
+                section: "FChunkHeader",
+                offset: start,
+                expected: chunk_header.header_size as usize,
+                actual: reader.tell() - start,
+                data_version: Some(version.to_i32() as u32)
+            })
         }
 
         Ok(chunk_header)
     }
 
+    /// This function is used to create the header of a chunk of the latest version, storing both of its hashes
+    pub fn new(guid: FGuid, rolling_hash: u64, sha_hash: FSHAHash, stored_as: EChunkStorageFlags, data_size_compressed: u32, data_size_uncompressed: u32) -> FChunkHeader {
+        FChunkHeader {
+            magic: CHUNK_MAGIC,
+            version: EChunkVersion::StoresDataSizeUncompressed,
+            header_size: CHUNK_HEADER_SIZE,
+            data_size_compressed,
+            guid,
+            rolling_hash,
+            stored_as,
+            hash_type: Some(EChunkHashFlags::Both),
+            data_size_uncompressed: Some(data_size_uncompressed),
+            sha_hash: Some(sha_hash),
+        }
+    }
+
+    /// This function is used to serialize the header, the fields written depend on its version the same way `parse` reads them
+    pub fn write(&self, writer: &mut ByteWriter) {
+        writer.write(&self.magic);
+        writer.write(&self.version.to_i32());
+        writer.write(&self.header_size);
+        writer.write(&self.data_size_compressed);
+        writer.write(&self.guid);
+        writer.write(&self.rolling_hash);
+        writer.write(&self.stored_as.bits());
+
+        if self.version.to_i32() >= EChunkVersion::StoresShaAndHashType.to_i32() {
+            writer.write(&self.sha_hash.clone().unwrap_or_default());
+            writer.write(&(self.hash_type.unwrap_or_default() as u8));
+        }
+
+        if self.version.to_i32() >= EChunkVersion::StoresDataSizeUncompressed.to_i32() {
+            writer.write(&self.data_size_uncompressed.unwrap_or_default());
+        }
+    }
+
     pub fn magic(&self) -> u32 {
         self.magic
     }
@@ -107,25 +148,53 @@ impl FChunkHeader {
     }
 
     pub fn is_compressed(&self) -> bool {
-        self.stored_as() == (EChunkStorageFlags::Compressed)
-    }
-
-    pub fn get_data(&self, reader:&mut ByteReader) -> Vec<u8> {
-        match self.stored_as {
-            EChunkStorageFlags::Compressed => {
-                let compressed_data = reader.read_remaining();
-                let mut decoder = flate2::read::ZlibDecoder::new(compressed_data.as_slice());
-                let mut buffer:Vec<u8> = Vec::with_capacity(self.data_size_uncompressed().map(|x| x as usize).unwrap_or(0));
-                decoder.read_to_end(&mut buffer).unwrap();
-
-                buffer
-            },
-            EChunkStorageFlags::None => {
-                reader.read_remaining()
-            },
-            _ => {
-                panic!("Unsupported storage type: {:?}", self.stored_as);
+        self.stored_as.is_compressed()
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.stored_as.is_encrypted()
+    }
+
+    /// This function is used to read the data following the header, decompressing it if needed
+    pub fn get_data(&self, reader:&mut ByteReader) -> ParseResult<Vec<u8>> {
+        self.get_data_with_decryptor(reader, None)
+    }
+
+    /// This function is used to read the data following the header, encrypted data is decrypted with `decryptor` before being decompressed
+    pub fn get_data_with_decryptor(&self, reader:&mut ByteReader, decryptor: Option<&dyn Decryptor>) -> ParseResult<Vec<u8>> {
+        let stored_data = reader.read_slice(self.data_size_compressed as usize)?;
+
+        let decrypted_data;
+        let stored_data = if self.is_encrypted() {
+            decrypted_data = decryptor.ok_or(ParseError::MissingDecryptor)?.decrypt(stored_data)?;
+            decrypted_data.as_slice()
+        } else {
+            stored_data
+        };
+
+        let mut data = if self.is_compressed() {
+            let mut decoder = flate2::read::ZlibDecoder::new(stored_data);
+            let mut buffer:Vec<u8> = Vec::with_capacity(self.data_size_uncompressed().map(|x| x as usize).unwrap_or(0));
+            decoder.read_to_end(&mut buffer).map_err(|_| ParseError::DecompressionError)?;
+
+            buffer
+        } else {
+            stored_data.to_vec()
+        };
+
+        // the cipher pads the data to its block size
+        if self.is_encrypted() && !self.is_compressed() {
+            if let Some(data_size_uncompressed) = self.data_size_uncompressed {
+                data.truncate(data_size_uncompressed as usize);
             }
         }
+
+        if let Some(data_size_uncompressed) = self.data_size_uncompressed {
+            if data.len() != data_size_uncompressed as usize {
+                return Err(ParseError::SizeMismatch)
+            }
+        }
+
+        Ok(data)
     }
-}
\ No newline at end of file
+}
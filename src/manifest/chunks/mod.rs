@@ -1 +1,3 @@
-pub mod chunk_header;
\ No newline at end of file
+pub mod chunk_header;
+pub mod chunk;
+pub mod chunk_database;
\ No newline at end of file
@@ -1,13 +1,14 @@
-use std::collections::HashMap;
+use indexmap::IndexMap;
 
-use crate::{error::ParseError, reader::ByteReader, ParseResult};
+use crate::{reader::ByteReader, writer::ByteWriter, ParseResult};
 
 
-#[derive(Debug, Clone, Default, serde::Serialize)]
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct FCustomFields {
-    _size:u32,
-    _version:u8,
-    pub fields:HashMap<String, String>
+    pub(crate) _size:u32,
+    pub(crate) _version:u8,
+    /// Fields are kept in the order they are stored in the manifest so they can be written back as they were
+    pub fields:IndexMap<String, String>
 }
 
 impl FCustomFields {
@@ -15,24 +16,24 @@ impl FCustomFields {
     pub fn parse(reader:&mut ByteReader) -> ParseResult<FCustomFields> {
         let start = reader.tell();
 
-        let size = reader.read()?;
-        let version = reader.read()?;
-        let count = reader.read()?;
-
-        let mut fields = HashMap::new();
-        fields.reserve(count as usize);
+        let size:u32 = reader.read()?;
+        let version:u8 = reader.read()?;
+        // every field is a key and a value, both at least a string length
+        let count = reader.read_count(4 + 4)?;
 
+        //like every other list of the manifest, keys and values are stored as two separate flat lists
+        let mut keys:Vec<String> = Vec::with_capacity(count);
         for _ in 0..count {
-            let key = reader.read()?;
-            let value = reader.read()?;
+            keys.push(reader.read()?);
+        }
 
+        let mut fields = IndexMap::with_capacity(count);
+        for key in keys {
+            let value = reader.read()?;
             fields.insert(key, value);
         }
 
-        if start + size as usize != reader.tell() {
-            println!("CustomFields size mismatch: expected {} but got {}", size, reader.tell() - start);
-            return Err(ParseError::SizeMismatch);
-        }
+        reader.end_section("FCustomFields", start, size as usize, Some(version as u32))?;
 
         Ok(FCustomFields {
             _size: size,
@@ -40,4 +41,55 @@ impl FCustomFields {
             fields
         })
     }
-}
\ No newline at end of file
+
+    /// This function is used to serialize Custom Fields into a ByteWriter
+    pub fn write(&self, writer:&mut ByteWriter) {
+        let start = writer.tell();
+
+        writer.write(&0u32); //size, filled once everything is written
+        writer.write(&self._version);
+        writer.write(&(self.fields.len() as u32));
+
+        for key in self.fields.keys() {
+            writer.write(key);
+        }
+
+        for value in self.fields.values() {
+            writer.write(value);
+        }
+
+        writer.write_u32_at(start, (writer.tell() - start) as u32);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_written_before_values() {
+        let custom_fields = FCustomFields {
+            _size: 0,
+            _version: 0,
+            fields: IndexMap::from([("b".to_owned(), "1".to_owned()), ("a".to_owned(), "22".to_owned())]),
+        };
+
+        let mut writer = ByteWriter::new();
+        custom_fields.write(&mut writer);
+        let data = writer.into_inner();
+
+        let expected: &[u8] = &[
+            34, 0, 0, 0, // size
+            0, // version
+            2, 0, 0, 0, // count
+            2, 0, 0, 0, b'b', 0,
+            2, 0, 0, 0, b'a', 0,
+            2, 0, 0, 0, b'1', 0,
+            3, 0, 0, 0, b'2', b'2', 0,
+        ];
+        assert_eq!(data, expected);
+
+        let parsed = FCustomFields::parse(&mut ByteReader::from_slice(&data)).unwrap();
+        assert_eq!(parsed.fields, custom_fields.fields);
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{chunk_info::FChunkInfo, file_manifest::FFileManifest, shared::FGuid, FManifest};
+
+/// This type describes what changed between two builds of the same app.
+/// Files are matched by filename and compared with `FFileManifest::hash`, size and flags, symlinks are compared with their target.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ManifestDiff<'a> {
+    added_files: Vec<&'a FFileManifest>,
+    removed_files: Vec<&'a FFileManifest>,
+    modified_files: Vec<&'a FFileManifest>,
+    unchanged_files: Vec<&'a FFileManifest>,
+    new_chunks: Vec<&'a FChunkInfo>,
+}
+
+impl<'a> ManifestDiff<'a> {
+    /// This function is used to compare an old build with a new one
+    pub fn new(old: &'a FManifest, new: &'a FManifest) -> ManifestDiff<'a> {
+        let old_files: HashMap<&str, &FFileManifest> = old.file_list.entries().iter().map(|file| (file.filename(), file)).collect();
+        let new_filenames: HashSet<&str> = new.file_list.entries().iter().map(|file| file.filename()).collect();
+
+        let mut added_files = vec![];
+        let mut modified_files = vec![];
+        let mut unchanged_files = vec![];
+
+        for file in new.file_list.entries() {
+            match old_files.get(file.filename()) {
+                None => added_files.push(file),
+                Some(old_file) if !is_same_file(old_file, file) => modified_files.push(file),
+                Some(_) => unchanged_files.push(file),
+            }
+        }
+
+        let removed_files = old.file_list.entries().iter().filter(|file| !new_filenames.contains(file.filename())).collect();
+
+        // only the chunks needed by files that have to be written are downloaded, unchanged files are already installed
+        let old_chunks: HashSet<&FGuid> = old.file_list.entries().iter().flat_map(|file| file.chunk_parts()).map(|part| part.guid()).collect();
+        let needed_chunks: HashSet<&FGuid> = added_files.iter().chain(modified_files.iter())
+            .flat_map(|file| file.chunk_parts())
+            .map(|part| part.guid())
+            .filter(|guid| !old_chunks.contains(guid))
+            .collect();
+
+        let new_chunks = new.chunk_list.chunks().iter().filter(|chunk| needed_chunks.contains(chunk.guid())).collect();
+
+        ManifestDiff {
+            added_files,
+            removed_files,
+            modified_files,
+            unchanged_files,
+            new_chunks,
+        }
+    }
+
+    /// Files that only exist in the new build
+    pub fn added_files(&self) -> &[&'a FFileManifest] {
+        &self.added_files
+    }
+
+    /// Files that only exist in the old build
+    pub fn removed_files(&self) -> &[&'a FFileManifest] {
+        &self.removed_files
+    }
+
+    /// Files of the new build whose content or attributes changed, or symlinks whose target changed
+    pub fn modified_files(&self) -> &[&'a FFileManifest] {
+        &self.modified_files
+    }
+
+    /// Files of the new build that are identical in the old build
+    pub fn unchanged_files(&self) -> &[&'a FFileManifest] {
+        &self.unchanged_files
+    }
+
+    /// Chunks of the new build needed by added and modified files that the old build doesn't reference
+    pub fn new_chunks(&self) -> &[&'a FChunkInfo] {
+        &self.new_chunks
+    }
+
+    /// Amount of bytes to download to update from the old build to the new one
+    pub fn download_size(&self) -> u64 {
+        self.new_chunks.iter().map(|chunk| chunk.compressed_size().max(0) as u64).sum()
+    }
+
+    /// Size of the files that have to be written to update from the old build to the new one
+    pub fn write_size(&self) -> u64 {
+        self.added_files.iter().chain(self.modified_files.iter()).map(|file| file.file_size()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added_files.is_empty() && self.removed_files.is_empty() && self.modified_files.is_empty()
+    }
+}
+
+// a file of the new build is unchanged when the old build installs exactly the same file
+fn is_same_file(old_file: &FFileManifest, new_file: &FFileManifest) -> bool {
+    if old_file.is_symlink() || new_file.is_symlink() {
+        return old_file.syslink_target() == new_file.syslink_target();
+    }
+
+    old_file.hash() == new_file.hash() && old_file.file_size() == new_file.file_size() && old_file.flags() == new_file.flags()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{manifest::builder::{EChunkingMode, ManifestBuilder}, test_utils::{random_data, TempDir}};
+
+    use super::*;
+
+    const CHUNK_SIZE: usize = 64 * 1024;
+
+    fn build(name: &str, files: &[(&str, Vec<u8>)]) -> FManifest {
+        let source = TempDir::new(&format!("diff_{}_source", name));
+        let cloud_dir = TempDir::new(&format!("diff_{}_cloud", name));
+
+        for (filename, data) in files {
+            source.write(filename, data);
+        }
+
+        ManifestBuilder::new("Test", name).with_chunking(EChunkingMode::Fixed, CHUNK_SIZE).build(source.path(), cloud_dir.path()).unwrap()
+    }
+
+    fn filenames(files: &[&FFileManifest]) -> Vec<String> {
+        let mut filenames: Vec<String> = files.iter().map(|file| file.filename().to_string()).collect();
+        filenames.sort();
+        filenames
+    }
+
+    #[test]
+    fn changes_between_builds_are_listed() {
+        // every file is a multiple of the chunk size, each chunk only holds the data of one file
+        let modified_old = random_data(3 * CHUNK_SIZE, 41);
+        let mut modified_new = modified_old.clone();
+        modified_new[CHUNK_SIZE + 10] ^= 0xFF;
+
+        let old = build("old", &[
+            ("unchanged.bin", random_data(2 * CHUNK_SIZE, 42)),
+            ("removed.bin", random_data(CHUNK_SIZE, 43)),
+            ("modified.bin", modified_old),
+        ]);
+        let new = build("new", &[
+            ("unchanged.bin", random_data(2 * CHUNK_SIZE, 42)),
+            ("modified.bin", modified_new),
+            ("added.bin", random_data(CHUNK_SIZE, 44)),
+        ]);
+
+        let diff = ManifestDiff::new(&old, &new);
+        assert!(!diff.is_empty());
+        assert_eq!(filenames(diff.added_files()), ["added.bin"]);
+        assert_eq!(filenames(diff.removed_files()), ["removed.bin"]);
+        assert_eq!(filenames(diff.modified_files()), ["modified.bin"]);
+        assert_eq!(filenames(diff.unchanged_files()), ["unchanged.bin"]);
+
+        // the first and last chunks of the modified file are already known
+        let added_chunk = diff.added_files()[0].chunk_parts()[0].guid();
+        let modified_chunk = diff.modified_files()[0].chunk_parts()[1].guid();
+        let new_chunks: HashSet<&FGuid> = diff.new_chunks().iter().map(|chunk| chunk.guid()).collect();
+        assert_eq!(new_chunks, HashSet::from([added_chunk, modified_chunk]));
+
+        assert_eq!(diff.download_size(), diff.new_chunks().iter().map(|chunk| chunk.compressed_size() as u64).sum::<u64>());
+        assert!(diff.download_size() > 0 && diff.download_size() < new.chunk_list.chunks().iter().map(|chunk| chunk.compressed_size() as u64).sum::<u64>());
+        assert_eq!(diff.write_size(), 4 * CHUNK_SIZE as u64);
+
+        assert!(ManifestDiff::new(&new, &new).is_empty());
+        assert!(ManifestDiff::new(&new, &new).new_chunks().is_empty());
+    }
+}
@@ -1,8 +1,8 @@
-use super::{chunk_part::FChunkPart, shared::{UnknownHash, FSHAHash, MD5_DIGEST_SIZE, SHA256_DIGEST_SIZE}};
+use super::{chunk_part::FChunkPart, shared::{EFileMetaFlags, UnknownHash, FSHAHash, MD5_DIGEST_SIZE, SHA256_DIGEST_SIZE}};
 
 
 
-#[derive(Debug, Default, Clone, serde::Serialize)]
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
 pub struct FFileManifest {
     pub(crate) filename: String,
     pub(crate) syslink_target: String,
@@ -13,7 +13,7 @@ pub struct FFileManifest {
     pub(crate) mime_type: Option<String>,
     pub(crate) hash_md5: Option<UnknownHash<MD5_DIGEST_SIZE>>,
     pub(crate) hash_sha256: Option<UnknownHash<SHA256_DIGEST_SIZE>>,
-    pub(crate) file_size: u32
+    pub(crate) file_size: u64
 }
 
 impl PartialEq for FFileManifest {
@@ -23,16 +23,21 @@ impl PartialEq for FFileManifest {
 }
 
 impl FFileManifest {
+    /// The attributes of the file, unknown bits are kept
+    pub fn flags(&self) -> EFileMetaFlags {
+        EFileMetaFlags::from_bits_retain(self.flags)
+    }
+
     pub fn read_only(&self) -> bool {
-        self.flags & 0x01 == 1
+        self.flags().contains(EFileMetaFlags::ReadOnly)
     }
 
     pub fn compressed(&self) -> bool {
-        self.flags & 0x02 == 1
+        self.flags().contains(EFileMetaFlags::Compressed)
     }
 
     pub fn executable(&self) -> bool {
-        self.flags & 0x04 == 1
+        self.flags().contains(EFileMetaFlags::UnixExecutable)
     }
 
     pub fn sha_hash(&self) -> &FSHAHash {
@@ -47,7 +52,8 @@ impl FFileManifest {
         self.hash_sha256.as_ref()
     }
 
-    pub fn file_size(&self) -> u32 {
+    /// Size of the file, the sum of the sizes of its chunk parts
+    pub fn file_size(&self) -> u64 {
         self.file_size
     }
 
@@ -59,6 +65,33 @@ impl FFileManifest {
         &self.syslink_target
     }
 
+    /// Symlinks have a target and no chunk parts, they are verified by their target instead of their hash
+    pub fn is_symlink(&self) -> bool {
+        !self.syslink_target.is_empty()
+    }
+
+    /// This function is used to get the filename a symlink points to, its target being relative to the directory of the symlink.
+    /// `None` is returned when the file is not a symlink, or when its target is absolute or points outside of the build.
+    pub fn symlink_target_filename(&self) -> Option<String> {
+        if !self.is_symlink() || self.syslink_target.starts_with(['/', '\\']) {
+            return None;
+        }
+
+        let mut components: Vec<&str> = self.filename.split(['/', '\\']).filter(|component| !component.is_empty() && *component != ".").collect();
+        components.pop()?;
+
+        for component in self.syslink_target.split(['/', '\\']) {
+            match component {
+                "" | "." => continue,
+                ".." => { components.pop()?; },
+                component if component.contains(':') => return None,
+                component => components.push(component),
+            }
+        }
+
+        Some(components.join("/"))
+    }
+
     pub fn mime_type(&self) -> Option<&str> {
         self.mime_type.as_deref()
     }
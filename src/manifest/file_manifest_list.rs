@@ -1,8 +1,8 @@
-use crate::{error::ParseError, reader::ByteReader, ParseResult};
-use super::{chunk_part::FChunkPart, file_manifest::FFileManifest, shared::UnknownHash};
+use crate::{reader::ByteReader, writer::ByteWriter, ParseResult};
+use super::{chunk_part::{FChunkPart, CHUNK_PART_SIZE}, file_manifest::FFileManifest, shared::{UnknownHash, SHA1_DIGEST_SIZE, SHA256_DIGEST_SIZE}};
 
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct FFileManifestList {
     pub(crate) _version: u8,
     pub(crate) _size:u32,
@@ -15,11 +15,12 @@ impl FFileManifestList {
     pub fn parse(reader: &mut ByteReader) -> ParseResult<FFileManifestList> {
         let reader_start = reader.tell();
 
-        let size = reader.read()?;
-        let version = reader.read()?;
-        let count = reader.read()?;
+        let size:u32 = reader.read()?;
+        let version:u8 = reader.read()?;
+        // every entry has at least a filename, a symlink target, a hash, flags, install tags and chunk parts
+        let count = reader.read_count(4 + 4 + SHA1_DIGEST_SIZE + 1 + 4 + 4)?;
 
-        let mut entries:Vec<FFileManifest> = vec![Default::default(); count as usize];
+        let mut entries:Vec<FFileManifest> = vec![Default::default(); count];
 
         for entry in entries.iter_mut() {
             entry.filename = reader.read()?;
@@ -42,11 +43,11 @@ impl FFileManifestList {
         }
 
          for entry in entries.iter_mut() {
-             let part_count = reader.read::<u32>()?;
+             let part_count = reader.read_count(CHUNK_PART_SIZE as usize)?;
              let mut file_offset = 0;
 
              //make sure we have enough capacity to push every parts without reallocating
-             entry.chunk_parts.reserve(part_count as usize - entry.chunk_parts.capacity());
+             entry.chunk_parts.reserve(part_count);
              for _ in 0..part_count {
                 let part = FChunkPart::parse(reader, file_offset)?;
                 file_offset += part.size() as usize;
@@ -74,22 +75,76 @@ impl FFileManifestList {
          }
 
         for entry in entries.iter_mut() {
-            entry.file_size = entry.chunk_parts.iter().map(|part| part.size()).sum();
+            entry.file_size = entry.chunk_parts.iter().map(|part| part.size() as u64).sum();
         }
 
-        if reader_start + size as usize != reader.tell() {
-            println!("FileManifestList size mismatch: expected {} but got {}\nFileManifestList version : {}", size, reader.tell() - reader_start, version);
-            return Err(ParseError::InvalidData);
-        }
+        reader.end_section("FFileManifestList", reader_start, size as usize, Some(version as u32))?;
 
         Ok(FFileManifestList {
             _version: version,
             _size: size,
-            _count: count,
+            _count: count as u32,
             entries
         })
     }
 
+    /// This function is used to serialize a FFileManifestList into a ByteWriter
+    pub fn write(&self, writer: &mut ByteWriter) {
+        let start = writer.tell();
+
+        writer.write(&0u32); //size, filled once everything is written
+        writer.write(&self._version);
+        writer.write(&(self.entries.len() as u32));
+
+        for entry in self.entries.iter() {
+            writer.write(&entry.filename);
+        }
+
+        for entry in self.entries.iter() {
+            writer.write(&entry.syslink_target);
+        }
+
+        for entry in self.entries.iter() {
+            writer.write(&entry.hash);
+        }
+
+        for entry in self.entries.iter() {
+            writer.write(&entry.flags);
+        }
+
+        for entry in self.entries.iter() {
+            writer.write_array(&entry.install_tags, |writer, tag| writer.write(tag));
+        }
+
+        for entry in self.entries.iter() {
+            writer.write_array(&entry.chunk_parts, |writer, part| part.write(writer));
+        }
+
+        if self._version >= 1 {
+            for entry in self.entries.iter() {
+                match &entry.hash_md5 {
+                    Some(hash) => {
+                        writer.write(&1u32);
+                        writer.write(hash);
+                    },
+                    None => writer.write(&0u32)
+                }
+            }
+
+            for entry in self.entries.iter() {
+                writer.write(entry.mime_type.as_deref().unwrap_or_default());
+            }
+        }
+
+        if self._version >= 2 {
+            for entry in self.entries.iter() {
+                writer.write(&entry.hash_sha256.clone().unwrap_or(UnknownHash::new([0; SHA256_DIGEST_SIZE])));
+            }
+        }
+
+        writer.write_u32_at(start, (writer.tell() - start) as u32);
+    }
+
     pub fn entries(&self) -> &Vec<FFileManifest> {
         &self.entries
     }
@@ -1,78 +1,149 @@
-use std::io::Read;
+use std::io::{Read, Write};
 
-use crate::{error::ParseError, reader::ByteReader, ParseResult};
+use crate::{crypto::Decryptor, error::ParseError, reader::ByteReader, writer::ByteWriter, ParseResult};
 
-use super::{shared::{EFeatureLevel, EManifestStorageFlags, FSHAHash}, FManifestParser};
-use flate2::read::ZlibDecoder;
+use super::shared::{EFeatureLevel, EManifestStorageFlags, FSHAHash};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
 
 pub const MANIFEST_MAGIC:u32 = 0x44BEC00C;
+pub const MANIFEST_HEADER_SIZE:u32 = 41;
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct FManifestHeader {
-    magic: u32,
-    header_size: u32,
-    data_size_uncompressed: u32,
-    data_size_compressed: u32,
-    sha_hash: FSHAHash,
-    stored_as: EManifestStorageFlags,
-    version: EFeatureLevel,
+    pub(crate) magic: u32,
+    pub(crate) header_size: u32,
+    pub(crate) data_size_uncompressed: u32,
+    pub(crate) data_size_compressed: u32,
+    pub(crate) sha_hash: FSHAHash,
+    pub(crate) stored_as: EManifestStorageFlags,
+    pub(crate) version: EFeatureLevel,
 }
 
 impl FManifestHeader {
-    pub fn parse(manifest:&mut FManifestParser) -> ParseResult<(FManifestHeader, ByteReader)> {
-        manifest.reader.seek(0);
-        let magic = manifest.reader.read()?;
+    /// This function is used to parse the header, the reader must be positioned at the beginning of the manifest
+    pub fn parse(reader:&mut ByteReader) -> ParseResult<FManifestHeader> {
+        let start = reader.tell();
+        let magic = reader.read()?;
 
         if magic != MANIFEST_MAGIC {
             return Err(ParseError::InvalidMagic)
         }
 
-        let header_size = manifest.reader.read()?;
-        let data_size_uncompressed = manifest.reader.read()?;
-        let data_size_compressed = manifest.reader.read()?;
-        let header_hash = manifest.reader.read()?;
+        let header_size = reader.read()?;
+        let data_size_uncompressed = reader.read()?;
+        let data_size_compressed = reader.read()?;
+        let sha_hash = reader.read()?;
 
-        let stored_as = EManifestStorageFlags::try_from(manifest.reader.read::<u8>()?).map_err(|_| ParseError::InvalidStorageFlag)?;
-        let version = EFeatureLevel::from_i32(manifest.reader.read()?).ok_or(ParseError::InvalidData)?;
+        let stored_as = EManifestStorageFlags::try_from(reader.read::<u8>()?)?;
+        let version = EFeatureLevel::from_i32(reader.read()?).ok_or(ParseError::InvalidData)?;
 
-        if header_size != manifest.reader.tell() as u32 {
-            dbg!(header_size, manifest.reader.tell() as u32);
-            return Err(ParseError::OffsetMismatch)
+        // in lenient mode, fields added to the header by newer versions are skipped
+        reader.end_section("FManifestHeader", start, header_size as usize, None)?;
+
+        Ok(FManifestHeader {
+            magic,
+            header_size,
+            data_size_uncompressed,
+            data_size_compressed,
+            sha_hash,
+            stored_as,
+            version
+        })
+    }
+
+    /// This function is used to read the manifest data that follows the header.
+    /// Compressed data is decompressed while it is read from `source`, so the uncompressed data is the only buffer allocated.
+    pub fn read_data(&self, source: impl Read) -> ParseResult<Vec<u8>> {
+        self.read_data_with_decryptor(source, None)
+    }
+
+    /// This function is used to read the manifest data like `read_data`, encrypted data is decrypted with `decryptor` before being decompressed
+    pub fn read_data_with_decryptor(&self, source: impl Read, decryptor: Option<&dyn Decryptor>) -> ParseResult<Vec<u8>> {
+        match self.read_data_partial(source, decryptor)? {
+            (_, Some(error)) => Err(error),
+            (buffer, None) => Ok(buffer)
+        }
+    }
+
+    /// This function is used to read as much of the manifest data as possible, for manifests that are truncated or corrupted.
+    /// The data that could be decoded is returned along with the error that stopped the decoding, if any.
+    pub fn read_data_partial(&self, source: impl Read, decryptor: Option<&dyn Decryptor>) -> ParseResult<(Vec<u8>, Option<ParseError>)> {
+        let mut stored_data = source.take(self.data_size_compressed as u64);
+
+        if !self.stored_as.is_encrypted() {
+            return self.decode_data(stored_data);
+        }
+
+        let decryptor = decryptor.ok_or(ParseError::MissingDecryptor)?;
+        let mut encrypted_data = Vec::with_capacity(self.data_size_compressed as usize);
+        stored_data.read_to_end(&mut encrypted_data)?;
+
+        let (mut buffer, error) = self.decode_data(&decryptor.decrypt(&encrypted_data)?[..])?;
+
+        // the cipher pads the data to its block size
+        if error.is_none() && !self.stored_as.is_compressed() {
+            buffer.truncate(self.data_size_uncompressed as usize);
+
+            if FSHAHash::new_from_hashable(&buffer[..]) != self.sha_hash {
+                return Ok((buffer, Some(ParseError::HashMismatch)))
+            }
         }
 
-        let data = manifest.reader.read_bytes(manifest.reader.length() - header_size as usize)?; //actual manifest data
-        let proper_data = if stored_as == EManifestStorageFlags::Compressed {
-            let mut decoder = ZlibDecoder::new(&data[..]);
-            let mut buffer:Vec<u8> = Vec::with_capacity(data_size_uncompressed as usize);
-            let length = decoder.read_to_end(&mut buffer).map_err(|_| ParseError::DecompressionError)?;
+        Ok((buffer, error))
+    }
 
-            if length != data_size_uncompressed as usize {
-                return Err(ParseError::DecompressionError)
+    fn decode_data(&self, mut stored_data: impl Read) -> ParseResult<(Vec<u8>, Option<ParseError>)> {
+        let mut buffer:Vec<u8> = Vec::with_capacity(self.data_size_uncompressed as usize);
+
+        if self.stored_as.is_compressed() {
+            // bytes decompressed before an error are kept in the buffer
+            let mut decoder = ZlibDecoder::new(stored_data);
+            if decoder.read_to_end(&mut buffer).is_err() || buffer.len() != self.data_size_uncompressed as usize {
+                return Ok((buffer, Some(ParseError::DecompressionError)))
             }
 
            let in_hash = FSHAHash::new_from_hashable(&buffer[..]);
-           
-           if in_hash != header_hash {
-               return Err(ParseError::HashMismatch)
+
+           if in_hash != self.sha_hash {
+               return Ok((buffer, Some(ParseError::HashMismatch)))
            }
+        } else {
+            stored_data.read_to_end(&mut buffer)?;
 
+            if buffer.len() < self.data_size_uncompressed as usize {
+                return Ok((buffer, Some(ParseError::SizeMismatch)))
+            }
+        }
+
+        Ok((buffer, None))
+    }
 
-            buffer
+    /// This function is used to serialize the header followed by the manifest data.
+    /// `data` is the uncompressed manifest data, sizes and SHA1 hash are computed from it the same way `parse` verifies them.
+    pub fn write(&self, writer:&mut ByteWriter, data:&[u8]) -> ParseResult<()> {
+        // writing encrypted manifests is not supported
+        if self.stored_as.is_encrypted() {
+            return Err(ParseError::InvalidStorageFlag)
+        }
+
+        let stored_data = if self.stored_as.is_compressed() {
+            let mut encoder = ZlibEncoder::new(Vec::with_capacity(data.len()), Compression::default());
+            encoder.write_all(data).map_err(|_| ParseError::CompressionError)?;
+            encoder.finish().map_err(|_| ParseError::CompressionError)?
         } else {
-            data
+            data.to_vec()
         };
 
-        let header = FManifestHeader {
-            magic,
-            header_size,
-            data_size_uncompressed,
-            data_size_compressed,
-            sha_hash: header_hash,
-            stored_as,
-            version
-        };
+        writer.write(&MANIFEST_MAGIC);
+        writer.write(&MANIFEST_HEADER_SIZE);
+        writer.write(&(data.len() as u32));
+        writer.write(&(stored_data.len() as u32));
+        writer.write(&FSHAHash::new_from_hashable(data));
+        writer.write(&self.stored_as.bits());
+        writer.write(&self.version.to_i32());
+        writer.write_bytes(&stored_data);
 
-        Ok((header, ByteReader::new(proper_data)))
+        Ok(())
     }
 
     pub fn version(&self) -> EFeatureLevel {
@@ -0,0 +1,340 @@
+// Parser for the legacy JSON manifest format.
+// Numbers, hashes and sizes are stored as "blobs": every byte of the little endian value is written as a 3 digit decimal number.
+
+use indexmap::IndexMap;
+
+use crate::{error::ParseError, ParseResult};
+
+use super::{
+    chunk_info::{data_group_number, FChunkInfo},
+    chunk_list::FChunkList,
+    chunk_part::FChunkPart,
+    custom_fields::FCustomFields,
+    file_manifest::FFileManifest,
+    file_manifest_list::FFileManifestList,
+    header::FManifestHeader,
+    meta::FManifestMeta,
+    shared::{EFeatureLevel, EFileMetaFlags, EManifestStorageFlags, FGuid, FSHAHash, SHA1_DIGEST_SIZE},
+    FManifest,
+};
+
+/// JSON manifests don't store the window size of their chunks, they were always 1MiB
+pub const JSON_CHUNK_WINDOW_SIZE: u32 = 1024 * 1024;
+
+// the version, file list and chunk list are required so that other JSON documents, like the serde export of FManifest, are not mistaken for an empty manifest
+#[derive(serde::Deserialize)]
+struct JsonManifest {
+    #[serde(rename = "ManifestFileVersion")]
+    manifest_file_version: String,
+    #[serde(rename = "bIsFileData", default)]
+    b_is_file_data: bool,
+    #[serde(rename = "AppID", default)]
+    app_id: Option<String>,
+    #[serde(rename = "AppNameString", default)]
+    app_name: String,
+    #[serde(rename = "BuildVersionString", default)]
+    build_version: String,
+    #[serde(rename = "LaunchExeString", default)]
+    launch_exe: String,
+    #[serde(rename = "LaunchCommand", default)]
+    launch_command: String,
+    #[serde(rename = "PrereqIds", default)]
+    prereq_ids: Vec<String>,
+    #[serde(rename = "PrereqName", default)]
+    prereq_name: String,
+    #[serde(rename = "PrereqPath", default)]
+    prereq_path: String,
+    #[serde(rename = "PrereqArgs", default)]
+    prereq_args: String,
+    #[serde(rename = "FileManifestList")]
+    file_manifest_list: Vec<JsonFileManifest>,
+    #[serde(rename = "ChunkHashList")]
+    chunk_hash_list: IndexMap<String, String>,
+    #[serde(rename = "ChunkShaList", default)]
+    chunk_sha_list: IndexMap<String, String>,
+    #[serde(rename = "DataGroupList", default)]
+    data_group_list: IndexMap<String, String>,
+    #[serde(rename = "ChunkFilesizeList", default)]
+    chunk_filesize_list: IndexMap<String, String>,
+    #[serde(rename = "CustomFields", default)]
+    custom_fields: IndexMap<String, String>,
+}
+
+#[derive(serde::Deserialize)]
+struct JsonFileManifest {
+    #[serde(rename = "Filename", default)]
+    filename: String,
+    #[serde(rename = "SymlinkTarget", default)]
+    symlink_target: String,
+    #[serde(rename = "FileHash")]
+    file_hash: String,
+    #[serde(rename = "bIsReadOnly", default)]
+    b_is_read_only: bool,
+    #[serde(rename = "bIsCompressed", default)]
+    b_is_compressed: bool,
+    #[serde(rename = "bIsUnixExecutable", default)]
+    b_is_unix_executable: bool,
+    #[serde(rename = "InstallTags", default)]
+    install_tags: Vec<String>,
+    #[serde(rename = "FileChunkParts", default)]
+    file_chunk_parts: Vec<JsonChunkPart>,
+}
+
+#[derive(serde::Deserialize)]
+struct JsonChunkPart {
+    #[serde(rename = "Guid")]
+    guid: String,
+    #[serde(rename = "Offset")]
+    offset: String,
+    #[serde(rename = "Size")]
+    size: String,
+}
+
+/// This function is used to check whether the data looks like a JSON manifest rather than a binary one
+pub fn is_json(data: &[u8]) -> bool {
+    let data = data.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(data);
+
+    data.iter()
+        .find(|byte| !byte.is_ascii_whitespace())
+        .is_some_and(|byte| *byte == b'{')
+}
+
+/// This function is used to decode a blob into its bytes
+fn blob_to_bytes(blob: &str) -> ParseResult<Vec<u8>> {
+    if !blob.len().is_multiple_of(3) || !blob.is_ascii() {
+        return Err(ParseError::InvalidData);
+    }
+
+    (0..blob.len())
+        .step_by(3)
+        .map(|i| blob[i..i + 3].parse::<u8>().map_err(|_| ParseError::InvalidData))
+        .collect()
+}
+
+/// This function is used to decode a blob into a number
+fn blob_to_num(blob: &str) -> ParseResult<u64> {
+    let bytes = blob_to_bytes(blob)?;
+
+    if bytes.len() > 8 {
+        return Err(ParseError::Overflow);
+    }
+
+    Ok(bytes.iter().enumerate().fold(0, |num, (i, byte)| num | (*byte as u64) << (i * 8)))
+}
+
+/// This function is used to parse a JSON manifest into the same FManifest structure as binary manifests
+pub fn parse(data: &[u8]) -> ParseResult<FManifest> {
+    let json: JsonManifest = serde_json::from_slice(data).map_err(|_| ParseError::InvalidJson)?;
+
+    let version = match EFeatureLevel::from_i32(blob_to_num(&json.manifest_file_version)? as i32).ok_or(ParseError::InvalidData)? {
+        EFeatureLevel::BrokenJsonVersion => EFeatureLevel::StoresChunkFileSizes,
+        version => version,
+    };
+
+    let header = FManifestHeader {
+        magic: 0,
+        header_size: 0,
+        data_size_uncompressed: data.len() as u32,
+        data_size_compressed: data.len() as u32,
+        sha_hash: FSHAHash::new_from_hashable(data),
+        stored_as: EManifestStorageFlags::empty(),
+        version,
+    };
+
+    let meta = FManifestMeta {
+        data_version: 0,
+        feature_level: version,
+        b_is_file_data: json.b_is_file_data,
+        app_id: json.app_id.as_deref().map(blob_to_num).transpose()?.unwrap_or(0) as u32,
+        app_name: json.app_name,
+        build_version: json.build_version,
+        launch_exe: json.launch_exe,
+        launch_command: json.launch_command,
+        prerequisites: vec![],
+        prereq_name: json.prereq_name,
+        prereq_path: json.prereq_path,
+        prereq_args: json.prereq_args,
+        build_id: None,
+        prereq_ids: json.prereq_ids,
+        uninstall_action_path: None,
+        uninstall_action_args: None,
+    };
+
+    let mut chunks = Vec::with_capacity(json.chunk_hash_list.len());
+    for (guid_str, hash) in json.chunk_hash_list.iter() {
+        let guid: FGuid = guid_str.parse()?;
+
+        let sha_hash = match json.chunk_sha_list.get(guid_str) {
+            Some(sha) => sha.parse()?,
+            None => FSHAHash::default(),
+        };
+
+        let group_num = match json.data_group_list.get(guid_str) {
+            Some(group) => blob_to_num(group)? as u8,
+            None => data_group_number(&guid),
+        };
+
+        let compressed_size = match json.chunk_filesize_list.get(guid_str) {
+            Some(size) => blob_to_num(size)? as i64,
+            None => 0,
+        };
+
+        chunks.push(FChunkInfo {
+            guid,
+            hash: blob_to_num(hash)?,
+            sha_hash,
+            group_num,
+            uncompressed_size: JSON_CHUNK_WINDOW_SIZE,
+            compressed_size,
+        });
+    }
+
+    let chunk_list = FChunkList {
+        _manifest_version: version,
+        _size: 0,
+        _version: 0,
+        chunks,
+    };
+
+    let mut entries = Vec::with_capacity(json.file_manifest_list.len());
+    for file in json.file_manifest_list {
+        let hash: [u8; SHA1_DIGEST_SIZE] = blob_to_bytes(&file.file_hash)?.try_into().map_err(|_| ParseError::InvalidData)?;
+
+        let mut file_offset = 0;
+        let mut chunk_parts = Vec::with_capacity(file.file_chunk_parts.len());
+        for part in file.file_chunk_parts {
+            let part = FChunkPart {
+                size: blob_to_num(&part.size)? as u32,
+                guid: part.guid.parse::<FGuid>()?,
+                offset: blob_to_num(&part.offset)? as u32,
+                file_offset,
+            };
+
+            file_offset += part.size as usize;
+            chunk_parts.push(part);
+        }
+
+        let mut flags = EFileMetaFlags::empty();
+        flags.set(EFileMetaFlags::ReadOnly, file.b_is_read_only);
+        flags.set(EFileMetaFlags::Compressed, file.b_is_compressed);
+        flags.set(EFileMetaFlags::UnixExecutable, file.b_is_unix_executable);
+
+        entries.push(FFileManifest {
+            filename: file.filename,
+            syslink_target: file.symlink_target,
+            hash: FSHAHash::new(hash),
+            flags: flags.bits(),
+            install_tags: file.install_tags,
+            file_size: chunk_parts.iter().map(|part| part.size as u64).sum(),
+            chunk_parts,
+            mime_type: None,
+            hash_md5: None,
+            hash_sha256: None,
+        });
+    }
+
+    let file_list = FFileManifestList {
+        _version: 0,
+        _size: 0,
+        _count: entries.len() as u32,
+        entries,
+    };
+
+    let custom_fields = FCustomFields {
+        _size: 0,
+        _version: 0,
+        fields: json.custom_fields,
+    };
+
+    Ok(FManifest {
+        header,
+        meta,
+        chunk_list,
+        file_list,
+        custom_fields,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEGACY_MANIFEST: &str = r#"{
+        "ManifestFileVersion": "013000000000",
+        "bIsFileData": false,
+        "AppID": "057048000000",
+        "AppNameString": "Test",
+        "BuildVersionString": "1.0",
+        "LaunchExeString": "Game.exe",
+        "LaunchCommand": "-nosplash",
+        "PrereqIds": [],
+        "FileManifestList": [
+            {
+                "Filename": "Game.exe",
+                "FileHash": "001002003004005006007008009010011012013014015016017018019020",
+                "bIsReadOnly": true,
+                "bIsUnixExecutable": true,
+                "InstallTags": ["core"],
+                "FileChunkParts": [
+                    { "Guid": "0123456789ABCDEF0123456789ABCDEF", "Offset": "000000000000", "Size": "232003000000" },
+                    { "Guid": "FEDCBA9876543210FEDCBA9876543210", "Offset": "232003000000", "Size": "024000000000" }
+                ]
+            }
+        ],
+        "ChunkHashList": {
+            "0123456789ABCDEF0123456789ABCDEF": "239205171137103069035001",
+            "FEDCBA9876543210FEDCBA9876543210": "001000000000000000000000"
+        },
+        "ChunkShaList": {
+            "0123456789ABCDEF0123456789ABCDEF": "0102030405060708090A0B0C0D0E0F1011121314"
+        },
+        "DataGroupList": {
+            "0123456789ABCDEF0123456789ABCDEF": "042"
+        },
+        "ChunkFilesizeList": {
+            "0123456789ABCDEF0123456789ABCDEF": "136019000000000000000000"
+        },
+        "CustomFields": { "CloudSaveFolder": "{AppData}/Test" }
+    }"#;
+
+    #[test]
+    fn legacy_manifest_is_parsed() {
+        assert!(is_json(LEGACY_MANIFEST.as_bytes()));
+        let manifest = parse(LEGACY_MANIFEST.as_bytes()).unwrap();
+
+        assert_eq!(manifest.meta.feature_level(), EFeatureLevel::StoresPrerequisiteIds);
+        assert_eq!(manifest.meta.app_id, 12345);
+        assert_eq!(manifest.meta.app_name, "Test");
+        assert_eq!(manifest.custom_fields.fields["CloudSaveFolder"], "{AppData}/Test");
+
+        let file = &manifest.file_list.entries()[0];
+        assert_eq!(file.filename(), "Game.exe");
+        assert_eq!(file.hash().data, core::array::from_fn::<u8, 20, _>(|index| index as u8 + 1));
+        assert_eq!(file.flags(), EFileMetaFlags::ReadOnly | EFileMetaFlags::UnixExecutable);
+        assert_eq!(file.install_tags(), &["core"]);
+        assert_eq!(file.file_size(), 1024);
+
+        let parts = file.chunk_parts();
+        assert_eq!((parts[0].offset(), parts[0].size(), parts[0].file_offset()), (0, 1000, 0));
+        assert_eq!((parts[1].offset(), parts[1].size(), parts[1].file_offset()), (1000, 24, 1000));
+        assert_eq!(parts[1].guid().to_string(), "FEDCBA9876543210FEDCBA9876543210");
+
+        let chunks = manifest.chunk_list.chunks();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].hash(), 0x0123456789ABCDEF);
+        assert_eq!(chunks[0].sha_hash().to_string(), "0102030405060708090a0b0c0d0e0f1011121314");
+        assert_eq!(chunks[0].group_num(), 42);
+        assert_eq!(chunks[0].compressed_size(), 5000);
+        assert_eq!(chunks[0].uncompressed_size(), JSON_CHUNK_WINDOW_SIZE);
+
+        // missing data groups are computed from the GUID
+        assert_eq!(chunks[1].group_num(), data_group_number(chunks[1].guid()));
+        assert_eq!(chunks[1].hash(), 1);
+    }
+
+    #[test]
+    fn other_json_objects_are_rejected() {
+        assert!(matches!(parse(b"{}"), Err(ParseError::InvalidJson)));
+        assert!(matches!(parse(br#"{ "ManifestFileVersion": "013000000000", "FileManifestList": [] }"#), Err(ParseError::InvalidJson)));
+    }
+}
@@ -1,29 +1,34 @@
-use crate::{error::ParseError, reader::ByteReader, ParseResult};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use sha1::{Digest, Sha1};
+
+use crate::{error::ParseError, reader::ByteReader, writer::ByteWriter, ParseResult};
 
 use super::shared::EFeatureLevel;
 
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct FManifestMeta {
-    feature_level:EFeatureLevel,
-    b_is_file_data:bool,
-    app_id:u32,
-    app_name:String,
-    build_version:String,
-    launch_exe:String,
-    launch_command:String,
-    prerequisites:Vec<String>,
-    prereq_name:String,
-    prereq_path:String,
-    prereq_args:String,
-    build_id:Option<String>,
-    prereq_ids:Vec<String>,
-    uninstall_action_path:Option<String>,
-    uninstall_action_args:Option<String>,
+    pub(crate) data_version:u8,
+    pub(crate) feature_level:EFeatureLevel,
+    pub(crate) b_is_file_data:bool,
+    pub(crate) app_id:u32,
+    pub(crate) app_name:String,
+    pub(crate) build_version:String,
+    pub(crate) launch_exe:String,
+    pub(crate) launch_command:String,
+    pub(crate) prerequisites:Vec<String>,
+    pub(crate) prereq_name:String,
+    pub(crate) prereq_path:String,
+    pub(crate) prereq_args:String,
+    pub(crate) build_id:Option<String>,
+    pub(crate) prereq_ids:Vec<String>,
+    pub(crate) uninstall_action_path:Option<String>,
+    pub(crate) uninstall_action_args:Option<String>,
 }
 
 impl FManifestMeta {
     pub fn parse(reader:&mut ByteReader) -> ParseResult<FManifestMeta> {
+        let start = reader.tell();
         let meta_size = reader.read::<u32>()?;
         let data_version = reader.read::<u8>()?;
 
@@ -42,6 +47,7 @@ impl FManifestMeta {
         let prereq_args = reader.read()?;
 
         let mut metadata = FManifestMeta {
+            data_version,
             feature_level,
             b_is_file_data,
             app_id,
@@ -71,14 +77,48 @@ impl FManifestMeta {
             metadata.uninstall_action_args = uninstall_action_args;
          }
 
-         if reader.tell() != meta_size as usize {
-            println!("Metadata size mismatch, {} bytes are missing, version : {}", meta_size - reader.tell() as u32, data_version);
-            return Err(ParseError::InvalidData);
-         }
+        reader.end_section("FManifestMeta", start, meta_size as usize, Some(data_version as u32))?;
 
         Ok(metadata)
     }
 
+    /// This function is used to serialize the FManifestMeta, fields are written according to the data version it was parsed with
+    pub fn write(&self, writer:&mut ByteWriter) {
+        let start = writer.tell();
+
+        writer.write(&0u32); //size, filled once everything is written
+        writer.write(&self.data_version);
+
+        writer.write(&self.feature_level.to_i32());
+        writer.write(&(self.b_is_file_data as u8));
+        writer.write(&self.app_id);
+
+        writer.write(&self.app_name);
+        writer.write(&self.build_version);
+        writer.write(&self.launch_exe);
+        writer.write(&self.launch_command);
+
+        writer.write_array(&self.prereq_ids, |writer, id| writer.write(id));
+        writer.write(&self.prereq_name);
+        writer.write(&self.prereq_path);
+        writer.write(&self.prereq_args);
+
+        if self.data_version >= 1 {
+            writer.write(self.build_id.as_deref().unwrap_or_default());
+        }
+
+        if self.data_version >= 2 {
+            writer.write(self.uninstall_action_path.as_deref().unwrap_or_default());
+            writer.write(self.uninstall_action_args.as_deref().unwrap_or_default());
+        }
+
+        writer.write_u32_at(start, (writer.tell() - start) as u32);
+    }
+
+    pub fn data_version(&self) -> u8 {
+        self.data_version
+    }
+
     pub fn app_id(&self) -> u32 {
         self.app_id
     }
@@ -115,10 +155,36 @@ impl FManifestMeta {
         &self.prereq_args
     }
 
+    /// The build id stored in the manifest, only available from meta data version 1. See `effective_build_id`
     pub fn build_id(&self) -> Option<&String> {
         self.build_id.as_ref()
     }
 
+    /// This function is used to compute the build id of manifests that don't store one, the same way BuildPatchServices does in `GetBackwardsCompatibleBuildId`:
+    /// the SHA1 of the app id and of the UTF-16 app name, build version, launch exe and launch command, encoded as URL-safe base64 without padding
+    pub fn backwards_compatible_build_id(&self) -> String {
+        let mut hasher = Sha1::new();
+        hasher.update(self.app_id.to_le_bytes());
+
+        for value in [&self.app_name, &self.build_version, &self.launch_exe, &self.launch_command] {
+            for character in value.encode_utf16() {
+                hasher.update(character.to_le_bytes());
+            }
+        }
+
+        URL_SAFE_NO_PAD.encode(hasher.finalize())
+    }
+
+    /// The stored build id, or the backwards compatible one for manifests that don't store it
+    pub fn effective_build_id(&self) -> String {
+        self.build_id.clone().unwrap_or_else(|| self.backwards_compatible_build_id())
+    }
+
+    /// This function is used to check if a build id refers to this manifest, either the stored build id or the backwards compatible one
+    pub fn matches_build_id(&self, build_id: &str) -> bool {
+        self.build_id.as_deref() == Some(build_id) || self.backwards_compatible_build_id() == build_id
+    }
+
     pub fn prereq_ids(&self) -> &Vec<String> {
         &self.prereq_ids
     }
@@ -138,4 +204,49 @@ impl FManifestMeta {
     pub fn is_file_data(&self) -> bool {
         self.b_is_file_data
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta(app_id: u32, app_name: &str, build_version: &str, launch_exe: &str, launch_command: &str) -> FManifestMeta {
+        FManifestMeta {
+            data_version: 0,
+            feature_level: EFeatureLevel::Latest,
+            b_is_file_data: false,
+            app_id,
+            app_name: app_name.to_owned(),
+            build_version: build_version.to_owned(),
+            launch_exe: launch_exe.to_owned(),
+            launch_command: launch_command.to_owned(),
+            prerequisites: vec![],
+            prereq_name: String::new(),
+            prereq_path: String::new(),
+            prereq_args: String::new(),
+            build_id: None,
+            prereq_ids: vec![],
+            uninstall_action_path: None,
+            uninstall_action_args: None,
+        }
+    }
+
+    #[test]
+    fn backwards_compatible_build_id_matches_known_values() {
+        assert_eq!(meta(0, "Test", "1.0", "", "").backwards_compatible_build_id(), "6dIJOoyMLKDMD-D7aDffbF3xAbY");
+        assert_eq!(meta(0, "Tést ✓", "1.0", "Game.exe", "-nosplash").backwards_compatible_build_id(), "0ouHaXnkyp5hHnG5yqZbYLHAenE");
+        assert_eq!(
+            meta(1, "Fortnite", "++Fortnite+Release-20.00-CL-19458861-Windows", "FortniteGame/Binaries/Win64/FortniteClient-Win64-Shipping.exe", "-AUTH_TYPE=exchangecode").backwards_compatible_build_id(),
+            "HNeyVfLOGyNy22vX6L6fOXm3ahQ"
+        );
+    }
+
+    #[test]
+    fn stored_build_id_takes_precedence() {
+        let mut meta = meta(0, "Test", "1.0", "", "");
+        assert_eq!(meta.effective_build_id(), "6dIJOoyMLKDMD-D7aDffbF3xAbY");
+
+        meta.build_id = Some("stored".to_owned());
+        assert_eq!(meta.effective_build_id(), "stored");
+        assert!(meta.matches_build_id("stored") && meta.matches_build_id("6dIJOoyMLKDMD-D7aDffbF3xAbY"));
+    }
+}
@@ -1,4 +1,6 @@
-use crate::{reader::ByteReader, ParseResult};
+use std::io::Read;
+
+use crate::{crypto::Decryptor, reader::ByteReader, writer::ByteWriter, ParseResult};
 
 pub mod header;
 pub mod shared;
@@ -10,32 +12,79 @@ pub mod file_manifest;
 pub mod chunk_part;
 pub mod custom_fields;
 pub mod chunks;
+pub mod json;
+pub mod diff;
+pub mod selection;
+pub mod builder;
+pub mod recovery;
 
-pub struct FManifestParser {
-    pub data: Vec<u8>,
-    pub reader: ByteReader
+/// This type is used to parse a manifest from any source implementing `Read`.
+/// Only the manifest data is buffered, compressed manifests are decompressed while they are read.
+pub struct FManifestParser<R: Read> {
+    source: R,
+    decryptor: Option<Box<dyn Decryptor>>
 }
 
-#[derive(Debug, Clone, serde::Serialize)]
+/// A parsed manifest. Its serde representation mirrors these structures and is not the legacy JSON manifest format:
+/// it can only be loaded back with serde, FManifestParser rejects it.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct FManifest {
     pub header: header::FManifestHeader,
     pub meta: meta::FManifestMeta,
     pub chunk_list: chunk_list::FChunkList,
     pub file_list: file_manifest_list::FFileManifestList,
-    pub custom_fields: custom_fields::FCustomFields,
-    pub data: Vec<u8>
+    pub custom_fields: custom_fields::FCustomFields
 }
 
-impl FManifestParser {
-    pub fn new(data: &[u8]) -> FManifestParser {
+impl FManifest {
+    /// This function is used to get the path of a chunk relative to the CloudDir, according to the feature level of this manifest
+    pub fn chunk_path(&self, chunk: &chunk_info::FChunkInfo) -> String {
+        chunk_info::chunk_path(chunk, self.meta.feature_level())
+    }
+
+    /// This function is used to get the URL of a chunk from the base URL of this build (its CloudDir)
+    pub fn chunk_url(&self, base_url: &str, chunk: &chunk_info::FChunkInfo) -> String {
+        chunk_info::chunk_url(base_url, chunk, self.meta.feature_level())
+    }
+}
+
+impl<'a> FManifestParser<&'a [u8]> {
+    /// Creates a parser borrowing the manifest, the data is not copied
+    pub fn new(data: &'a [u8]) -> FManifestParser<&'a [u8]> {
         FManifestParser {
-            data: data.to_vec(),
-            reader: ByteReader::new(data.to_vec())
+            source: data,
+            decryptor: None
         }
     }
+}
 
+impl<R: Read> FManifestParser<R> {
+    /// Creates a parser reading the manifest from a stream, such as a file or a HTTP response
+    pub fn from_reader(source: R) -> FManifestParser<R> {
+        FManifestParser {
+            source,
+            decryptor: None
+        }
+    }
+
+    /// This function is used to set the decryptor used when the manifest data is encrypted
+    pub fn with_decryptor(mut self, decryptor: impl Decryptor + 'static) -> FManifestParser<R> {
+        self.decryptor = Some(Box::new(decryptor));
+        self
+    }
+
+    /// This function is used to parse the manifest, JSON manifests are detected and parsed into the same structure as binary ones
     pub fn parse(mut self) -> ParseResult<FManifest> {
-        let (header, mut reader) = header::FManifestHeader::parse(&mut self)?;
+        let mut header_data = Vec::with_capacity(header::MANIFEST_HEADER_SIZE as usize);
+        (&mut self.source).take(header::MANIFEST_HEADER_SIZE as u64).read_to_end(&mut header_data)?;
+
+        if json::is_json(&header_data) {
+            self.source.read_to_end(&mut header_data)?;
+            return json::parse(&header_data);
+        }
+
+        let header = header::FManifestHeader::parse(&mut ByteReader::from_slice(&header_data))?;
+        let mut reader = ByteReader::new(header.read_data_with_decryptor(self.source, self.decryptor.as_deref())?);
 
         let meta = meta::FManifestMeta::parse(&mut reader)?;
         let chunk_header = chunk_list::FChunkList::parse(&mut reader, header.version())?;
@@ -47,8 +96,161 @@ impl FManifestParser {
             meta,
             chunk_list: chunk_header,
             file_list,
-            custom_fields,
-            data: self.data
+            custom_fields
         })
     }
-}
\ No newline at end of file
+
+    /// This function is used to parse the manifest in lenient mode, for truncated manifests or manifests using newer data versions.
+    /// Unknown trailing data of a section is skipped using its stored size, sections that fail to decode are skipped as well
+    /// and every problem is recorded as a warning. Only problems preventing the header from being read are returned as errors.
+    pub fn parse_lenient(mut self) -> ParseResult<recovery::PartialManifest> {
+        let mut header_data = Vec::with_capacity(header::MANIFEST_HEADER_SIZE as usize);
+        (&mut self.source).take(header::MANIFEST_HEADER_SIZE as u64).read_to_end(&mut header_data)?;
+
+        if json::is_json(&header_data) {
+            self.source.read_to_end(&mut header_data)?;
+            let manifest = json::parse(&header_data)?;
+
+            return Ok(recovery::PartialManifest {
+                header: manifest.header,
+                meta: Some(manifest.meta),
+                chunk_list: Some(manifest.chunk_list),
+                file_list: Some(manifest.file_list),
+                custom_fields: Some(manifest.custom_fields),
+                warnings: vec![],
+            });
+        }
+
+        // newer headers may be larger, the whole header has to be read before the manifest data
+        let header_size = header_data.get(4..8).map_or(0, |size| u32::from_le_bytes(size.try_into().unwrap_or_default()));
+        if header_size > header::MANIFEST_HEADER_SIZE {
+            (&mut self.source).take((header_size - header::MANIFEST_HEADER_SIZE) as u64).read_to_end(&mut header_data)?;
+        }
+
+        let mut header_reader = ByteReader::from_slice(&header_data);
+        header_reader.set_lenient(header_size >= header::MANIFEST_HEADER_SIZE);
+
+        let header = header::FManifestHeader::parse(&mut header_reader)?;
+        let (data, error) = header.read_data_partial(self.source, self.decryptor.as_deref())?;
+
+        Ok(recovery::PartialManifest::parse(header, data, header_reader.take_warnings().into_iter().chain(error)))
+    }
+}
+
+/// This type is used to serialize a FManifest back into the binary manifest format.
+/// An unmodified manifest is written back with the exact same manifest data. Compressed manifests
+/// are only byte-identical to Epic's output when the `zlib` feature is enabled, as the default
+/// flate2 backend produces a different (but equally valid) zlib stream.
+pub struct ManifestWriter<'a> {
+    manifest: &'a FManifest
+}
+
+impl<'a> ManifestWriter<'a> {
+    pub fn new(manifest: &'a FManifest) -> ManifestWriter<'a> {
+        ManifestWriter {
+            manifest
+        }
+    }
+
+    pub fn write(self) -> ParseResult<Vec<u8>> {
+        let mut data = ByteWriter::new();
+
+        self.manifest.meta.write(&mut data);
+        self.manifest.chunk_list.write(&mut data);
+        self.manifest.file_list.write(&mut data);
+        self.manifest.custom_fields.write(&mut data);
+
+        let mut writer = ByteWriter::new();
+        self.manifest.header.write(&mut writer, &data.into_inner())?;
+
+        Ok(writer.into_inner())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use crate::{error::ParseError, test_utils::{random_data, TempDir}};
+
+    use super::*;
+
+    fn build_manifest() -> FManifest {
+        let source = TempDir::new("manifest_source");
+        let cloud_dir = TempDir::new("manifest_cloud");
+
+        source.write("Game.exe", &random_data(3000, 6));
+        source.write("Content/Paks/data.pak", &random_data(5000, 7));
+
+        builder::ManifestBuilder::new("Test", "1.0")
+            .with_launch_exe("Game.exe", "-nosplash")
+            .with_custom_field("CloudSaveFolder", "{AppData}/Test")
+            .with_custom_field("ReleaseVersion", "1.0.0")
+            .build(source.path(), cloud_dir.path())
+            .unwrap()
+    }
+
+    fn assert_round_trip(stored_as: shared::EManifestStorageFlags) {
+        let mut manifest = build_manifest();
+        manifest.header.stored_as = stored_as;
+        let data = ManifestWriter::new(&manifest).write().unwrap();
+
+        let parsed = FManifestParser::new(&data).parse().unwrap();
+        assert_eq!(parsed.header.stored_as(), stored_as);
+        assert_eq!(ManifestWriter::new(&parsed).write().unwrap(), data);
+    }
+
+    #[test]
+    fn compressed_manifest_is_written_back_identically() {
+        assert_round_trip(shared::EManifestStorageFlags::Compressed);
+    }
+
+    #[test]
+    fn uncompressed_manifest_is_written_back_identically() {
+        assert_round_trip(shared::EManifestStorageFlags::empty());
+    }
+
+    #[test]
+    fn serde_export_is_loaded_back_with_serde_only() {
+        let manifest = build_manifest();
+        let export = serde_json::to_vec(&manifest).unwrap();
+
+        let imported: FManifest = serde_json::from_slice(&export).unwrap();
+        assert_eq!(ManifestWriter::new(&imported).write().unwrap(), ManifestWriter::new(&manifest).write().unwrap());
+
+        assert!(matches!(FManifestParser::new(&export).parse(), Err(ParseError::InvalidJson)));
+    }
+
+    #[test]
+    fn impossible_element_counts_are_rejected_before_allocating() {
+        let mut manifest = build_manifest();
+        manifest.header.stored_as = shared::EManifestStorageFlags::empty();
+        let mut data = ManifestWriter::new(&manifest).write().unwrap();
+
+        // the chunk list follows the meta section, its count is after its size and version
+        let meta_start = header::MANIFEST_HEADER_SIZE as usize;
+        let meta_size = u32::from_le_bytes(data[meta_start..meta_start + 4].try_into().unwrap()) as usize;
+        let count_offset = meta_start + meta_size + 5;
+        data[count_offset..count_offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        assert!(matches!(FManifestParser::new(&data).parse(), Err(ParseError::Overflow)));
+
+        let manifest = FManifestParser::new(&data).parse_lenient().unwrap();
+        assert!(matches!(manifest.warnings[..], [ParseError::Overflow]));
+        assert!(manifest.chunk_list.is_none());
+        assert_eq!(manifest.file_list.unwrap().entries().len(), 2);
+    }
+
+    #[test]
+    fn unknown_header_fields_are_skipped_in_lenient_mode() {
+        let mut data = ManifestWriter::new(&build_manifest()).write().unwrap();
+
+        // a newer header with 4 more bytes
+        data[4..8].copy_from_slice(&(header::MANIFEST_HEADER_SIZE + 4).to_le_bytes());
+        data.splice(header::MANIFEST_HEADER_SIZE as usize..header::MANIFEST_HEADER_SIZE as usize, [0xAA; 4]);
+
+        assert!(matches!(FManifestParser::new(&data).parse(), Err(ParseError::SectionSizeMismatch { section: "FManifestHeader", .. })));
+
+        let manifest = FManifestParser::new(&data).parse_lenient().unwrap();
+        assert!(matches!(manifest.warnings[..], [ParseError::SectionSizeMismatch { section: "FManifestHeader", expected: 45, actual: 41, .. }]));
+        assert_eq!(manifest.header.header_size(), 45);
+        assert_eq!(manifest.into_manifest().unwrap().file_list.entries().len(), 2);
+    }
+}
@@ -0,0 +1,93 @@
+use crate::{error::ParseError, reader::ByteReader, ParseResult};
+
+use super::{chunk_list::FChunkList, custom_fields::FCustomFields, file_manifest_list::FFileManifestList, header::FManifestHeader, meta::FManifestMeta, FManifest};
+
+/// This type is the result of a lenient parse: every section that could be decoded, and the problems found along the way.
+/// A section is `None` when it could not be decoded, usually because the manifest is truncated.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PartialManifest {
+    pub header: FManifestHeader,
+    pub meta: Option<FManifestMeta>,
+    pub chunk_list: Option<FChunkList>,
+    pub file_list: Option<FFileManifestList>,
+    pub custom_fields: Option<FCustomFields>,
+    #[serde(skip)]
+    pub warnings: Vec<ParseError>,
+}
+
+impl PartialManifest {
+    /// This function is used to parse the sections of the manifest data in lenient mode.
+    /// `warnings` are the problems found while reading the header and the manifest data.
+    pub(crate) fn parse(header: FManifestHeader, data: Vec<u8>, warnings: impl IntoIterator<Item = ParseError>) -> PartialManifest {
+        let mut reader = ByteReader::new(data);
+        reader.set_lenient(true);
+
+        for warning in warnings {
+            reader.warn(warning);
+        }
+
+        let meta = parse_section(&mut reader, FManifestMeta::parse);
+        let chunk_list = parse_section(&mut reader, |reader| FChunkList::parse(reader, header.version()));
+        let file_list = parse_section(&mut reader, FFileManifestList::parse);
+
+        // manifests older than custom fields simply end here
+        let custom_fields = parse_section(&mut reader, FCustomFields::parse);
+
+        if reader.tell() < reader.length() {
+            let offset = reader.tell();
+            reader.warn(ParseError::TrailingData {
+                offset,
+                size: reader.length() - offset
+            });
+        }
+
+        PartialManifest {
+            header,
+            meta,
+            chunk_list,
+            file_list,
+            custom_fields,
+            warnings: reader.take_warnings(),
+        }
+    }
+
+    /// This function is used to check if every section was decoded without any warning
+    pub fn is_complete(&self) -> bool {
+        self.warnings.is_empty() && self.meta.is_some() && self.chunk_list.is_some() && self.file_list.is_some() && self.custom_fields.is_some()
+    }
+
+    /// This function is used to get a FManifest when the meta, chunk list and file list sections were decoded.
+    /// Missing custom fields are left empty.
+    pub fn into_manifest(self) -> Option<FManifest> {
+        Some(FManifest {
+            header: self.header,
+            meta: self.meta?,
+            chunk_list: self.chunk_list?,
+            file_list: self.file_list?,
+            custom_fields: self.custom_fields.unwrap_or_default(),
+        })
+    }
+}
+
+// a section that fails to decode is skipped using its stored size, so the following sections can still be read
+fn parse_section<T>(reader: &mut ByteReader, parse: impl FnOnce(&mut ByteReader) -> ParseResult<T>) -> Option<T> {
+    let start = reader.tell();
+    if start >= reader.length() {
+        return None;
+    }
+
+    match parse(reader) {
+        Ok(section) => Some(section),
+        Err(error) => {
+            reader.warn(error);
+            reader.seek(start);
+
+            match reader.read::<u32>() {
+                Ok(size) if size >= 4 && start + size as usize <= reader.length() => reader.seek(start + size as usize),
+                _ => reader.seek(reader.length()),
+            }
+
+            None
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::{BTreeSet, HashSet};
+
+use super::{chunk_info::FChunkInfo, file_manifest::FFileManifest, shared::FGuid, FManifest};
+
+/// This type describes what has to be installed for a set of selected install tags.
+/// Like the launcher, files without any install tag are always part of the install, other files
+/// are only installed when at least one of their tags is selected.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct InstallSelection<'a> {
+    files: Vec<&'a FFileManifest>,
+    required_chunks: Vec<&'a FChunkInfo>,
+}
+
+impl<'a> InstallSelection<'a> {
+    /// This function is used to select the files and chunks needed to install `manifest` with the given tags
+    pub fn new<S: AsRef<str>>(manifest: &'a FManifest, tags: &[S]) -> InstallSelection<'a> {
+        let tags: HashSet<&str> = tags.iter().map(|tag| tag.as_ref()).collect();
+
+        let files: Vec<&FFileManifest> = manifest.file_list.entries().iter()
+            .filter(|file| is_file_selected(file, &tags))
+            .collect();
+
+        let needed_chunks: HashSet<&FGuid> = files.iter().flat_map(|file| file.chunk_parts()).map(|part| part.guid()).collect();
+        let required_chunks = manifest.chunk_list.chunks().iter().filter(|chunk| needed_chunks.contains(chunk.guid())).collect();
+
+        InstallSelection {
+            files,
+            required_chunks,
+        }
+    }
+
+    /// Files to install, in the order of the manifest
+    pub fn files(&self) -> &[&'a FFileManifest] {
+        &self.files
+    }
+
+    /// Chunks referenced by the selected files, in the order of the manifest
+    pub fn required_chunks(&self) -> &[&'a FChunkInfo] {
+        &self.required_chunks
+    }
+
+    /// Size of the selected files once installed
+    pub fn install_size(&self) -> u64 {
+        self.files.iter().map(|file| file.file_size()).sum()
+    }
+
+    /// Amount of bytes to download to install the selected files
+    pub fn download_size(&self) -> u64 {
+        self.required_chunks.iter().map(|chunk| chunk.compressed_size().max(0) as u64).sum()
+    }
+}
+
+/// This function is used to list every install tag used by the files of a manifest, sorted and without duplicates
+pub fn available_tags(manifest: &FManifest) -> Vec<&str> {
+    let tags: BTreeSet<&str> = manifest.file_list.entries().iter()
+        .flat_map(|file| file.install_tags())
+        .map(|tag| tag.as_str())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+
+    tags.into_iter().collect()
+}
+
+// the launcher stores an empty tag on files that are part of the base install, they are treated as untagged
+fn is_file_selected(file: &FFileManifest, tags: &HashSet<&str>) -> bool {
+    file.install_tags().iter().all(|tag| tag.is_empty())
+        || file.install_tags().iter().any(|tag| tags.contains(tag.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{manifest::builder::{EChunkingMode, ManifestBuilder}, test_utils::{random_data, TempDir}};
+
+    use super::*;
+
+    const CHUNK_SIZE: usize = 64 * 1024;
+
+    fn build() -> FManifest {
+        let source = TempDir::new("selection_source");
+        let cloud_dir = TempDir::new("selection_cloud");
+
+        // every file is a multiple of the chunk size, each chunk only holds the data of one file
+        let files: [(&str, &[&str]); 5] = [
+            ("base.bin", &[]),
+            ("base_empty_tag.bin", &[""]),
+            ("hd.bin", &["hd"]),
+            ("voice_en.bin", &["voice_en"]),
+            ("hd_voice_en.bin", &["hd", "voice_en"]),
+        ];
+        for (index, (filename, _)) in files.iter().enumerate() {
+            source.write(filename, &random_data(CHUNK_SIZE, 51 + index as u64));
+        }
+
+        let mut manifest = ManifestBuilder::new("Test", "1").with_chunking(EChunkingMode::Fixed, CHUNK_SIZE).build(source.path(), cloud_dir.path()).unwrap();
+        for file in manifest.file_list.entries.iter_mut() {
+            let (_, tags) = files.iter().find(|(filename, _)| *filename == file.filename()).unwrap();
+            file.install_tags = tags.iter().map(|tag| tag.to_string()).collect();
+        }
+
+        manifest
+    }
+
+    fn filenames(selection: &InstallSelection) -> HashSet<String> {
+        selection.files().iter().map(|file| file.filename().to_string()).collect()
+    }
+
+    #[test]
+    fn untagged_files_are_always_selected() {
+        let manifest = build();
+        let selection = InstallSelection::new(&manifest, &[] as &[&str]);
+
+        assert_eq!(filenames(&selection), HashSet::from(["base.bin".to_string(), "base_empty_tag.bin".to_string()]));
+        assert_eq!(selection.install_size(), 2 * CHUNK_SIZE as u64);
+        assert_eq!(available_tags(&manifest), ["hd", "voice_en"]);
+    }
+
+    #[test]
+    fn chunks_of_the_selected_tags_are_required() {
+        let manifest = build();
+        let selection = InstallSelection::new(&manifest, &["hd"]);
+
+        assert_eq!(filenames(&selection), HashSet::from(["base.bin", "base_empty_tag.bin", "hd.bin", "hd_voice_en.bin"].map(String::from)));
+
+        let expected_chunks: HashSet<&FGuid> = selection.files().iter().flat_map(|file| file.chunk_parts()).map(|part| part.guid()).collect();
+        let required_chunks: HashSet<&FGuid> = selection.required_chunks().iter().map(|chunk| chunk.guid()).collect();
+        assert_eq!(required_chunks, expected_chunks);
+        assert_eq!(required_chunks.len(), 4);
+
+        let voice_en = manifest.file_list.entries().iter().find(|file| file.filename() == "voice_en.bin").unwrap();
+        let voice_en_chunk = manifest.chunk_list.chunks().iter().find(|chunk| chunk.guid() == voice_en.chunk_parts()[0].guid()).unwrap();
+        let total_size: u64 = manifest.chunk_list.chunks().iter().map(|chunk| chunk.compressed_size() as u64).sum();
+        assert_eq!(selection.download_size(), total_size - voice_en_chunk.compressed_size() as u64);
+        assert_eq!(selection.install_size(), 4 * CHUNK_SIZE as u64);
+
+        assert_eq!(InstallSelection::new(&manifest, &["hd", "voice_en"]).download_size(), total_size);
+    }
+}
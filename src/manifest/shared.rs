@@ -2,7 +2,7 @@ use std::{collections::hash_map::DefaultHasher, fmt::Formatter, hash::{Hash, Has
 
 use sha1::{Sha1, Digest};
 
-use crate::{helper, reader::ByteReader, ParseResult};
+use crate::{error::ParseError, helper, reader::ByteReader, ParseResult};
 
 pub const SHA1_DIGEST_SIZE:usize = 20;
 pub const MD5_DIGEST_SIZE:usize = 16;
@@ -46,55 +46,126 @@ impl ToString for FGuid {
     }
 }
 
-#[derive(Debug, PartialEq, Copy, Clone, serde::Serialize)]
-pub enum EManifestStorageFlags {
-    // Stored as raw data.
-    None = 0,
-    // Flag for compressed data.
-    Compressed = 1,
-    // Flag for encrypted. If also compressed, decrypt first. Encryption will ruin compressibility.
-    Encrypted = 1 << 1,
+impl std::str::FromStr for FGuid {
+    type Err = ParseError;
+
+    /// Parses a GUID from its 32 hexadecimal characters representation, as returned by `to_string`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 32 || !s.is_ascii() {
+            return Err(ParseError::InvalidData);
+        }
+
+        let component = |index:usize| u32::from_str_radix(&s[index * 8..(index + 1) * 8], 16).map_err(|_| ParseError::InvalidData);
+
+        Ok(FGuid {
+            a: component(0)?,
+            b: component(1)?,
+            c: component(2)?,
+            d: component(3)?
+        })
+    }
 }
 
-impl From<u8> for EManifestStorageFlags {
-    fn from(value: u8) -> Self {
-        match value {
-            0 => EManifestStorageFlags::None,
-            1 => EManifestStorageFlags::Compressed,
-            2 => EManifestStorageFlags::Encrypted,
-            _ => panic!("Invalid EManifestStorageFlags value")
+impl<'de> serde::Deserialize<'de> for FGuid {
+    /// Accepts both the `{a, b, c, d}` layout written by `Serialize` and the 32 hexadecimal characters string
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(serde::Deserialize)]
+        struct Components {
+            a:u32,
+            b:u32,
+            c:u32,
+            d:u32
+        }
+
+        #[derive(serde::Deserialize)]
+        #[serde(untagged)]
+        enum GuidRepr {
+            Hex(String),
+            Components(Components)
+        }
+
+        match GuidRepr::deserialize(deserializer)? {
+            GuidRepr::Hex(hex) => hex.parse().map_err(|_| serde::de::Error::invalid_value(serde::de::Unexpected::Str(&hex), &"a 32 characters hexadecimal GUID")),
+            GuidRepr::Components(Components { a, b, c, d }) => Ok(FGuid { a, b, c, d })
         }
     }
 }
 
-#[derive(Debug, PartialEq, Copy, Clone, serde::Serialize)]
-pub enum EChunkStorageFlags
-{
-    None ,
-    // Flag for compressed data.
-    Compressed,
-    // Flag for encrypted. If also compressed, decrypt first. Encryption will ruin compressibility.
-    Encrypted,
+bitflags::bitflags! {
+    /// Attributes of a file of the manifest, stored in `FFileManifest::raw_flags`
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+    pub struct EFileMetaFlags: u8 {
+        // The file is installed read-only.
+        const ReadOnly = 1;
+        // The file is stored compressed (file data builds).
+        const Compressed = 1 << 1;
+        // The file is installed with the executable permission on Unix systems.
+        const UnixExecutable = 1 << 2;
+    }
 }
 
-impl Default for EChunkStorageFlags {
-    fn default() -> Self {
-        EChunkStorageFlags::None
+bitflags::bitflags! {
+    /// How the manifest data following FManifestHeader is stored, no flag means raw data
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, serde::Serialize, serde::Deserialize)]
+    pub struct EManifestStorageFlags: u8 {
+        // Flag for compressed data.
+        const Compressed = 1;
+        // Flag for encrypted. If also compressed, decrypt first. Encryption will ruin compressibility.
+        const Encrypted = 1 << 1;
     }
 }
 
-impl From<u8> for EChunkStorageFlags {
-    fn from(value: u8) -> Self {
-        match value {
-            0 => EChunkStorageFlags::None,
-            1 => EChunkStorageFlags::Compressed,
-            2 => EChunkStorageFlags::Encrypted,
-            _ => panic!("Invalid EChunkStorageFlags value")
-        }
+impl EManifestStorageFlags {
+    pub fn is_compressed(&self) -> bool {
+        self.contains(EManifestStorageFlags::Compressed)
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.contains(EManifestStorageFlags::Encrypted)
+    }
+}
+
+impl TryFrom<u8> for EManifestStorageFlags {
+    type Error = ParseError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        EManifestStorageFlags::from_bits(value).ok_or(ParseError::InvalidStorageFlag)
+    }
+}
+
+bitflags::bitflags! {
+    /// How the data following FChunkHeader is stored, no flag means raw data
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, serde::Serialize, serde::Deserialize)]
+    pub struct EChunkStorageFlags: u8 {
+        // Flag for compressed data.
+        const Compressed = 1;
+        // Flag for encrypted. If also compressed, decrypt first. Encryption will ruin compressibility.
+        const Encrypted = 1 << 1;
+    }
+}
+
+impl EChunkStorageFlags {
+    pub fn is_compressed(&self) -> bool {
+        self.contains(EChunkStorageFlags::Compressed)
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.contains(EChunkStorageFlags::Encrypted)
+    }
+}
+
+impl TryFrom<u8> for EChunkStorageFlags {
+    type Error = ParseError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        EChunkStorageFlags::from_bits(value).ok_or(ParseError::InvalidStorageFlag)
     }
 }
 
-#[derive(Debug, PartialEq, Copy, Clone, serde::Serialize)]
+#[derive(Debug, PartialEq, Copy, Clone, serde::Serialize, serde::Deserialize)]
 pub enum EChunkHashFlags
 {
     None,
@@ -108,14 +179,16 @@ pub enum EChunkHashFlags
     Both
 }
 
-impl From<u8> for EChunkHashFlags {
-    fn from(value: u8) -> Self {
+impl TryFrom<u8> for EChunkHashFlags {
+    type Error = ParseError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
         match value {
-            0 => EChunkHashFlags::None,
-            1 => EChunkHashFlags::RollingPoly64,
-            2 => EChunkHashFlags::Sha1,
-            3 => EChunkHashFlags::Both,
-            _ => panic!("Invalid EChunkHashFlags value")
+            0 => Ok(EChunkHashFlags::None),
+            1 => Ok(EChunkHashFlags::RollingPoly64),
+            2 => Ok(EChunkHashFlags::Sha1),
+            3 => Ok(EChunkHashFlags::Both),
+            _ => Err(ParseError::InvalidData)
         }
     }
 }
@@ -127,7 +200,7 @@ impl Default for EChunkHashFlags {
 }
 
 
-#[derive(Debug, Clone, Copy, serde::Serialize)]
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
 pub enum EChunkVersion
 {
     Invalid,
@@ -180,7 +253,7 @@ impl PartialEq for EChunkVersion {
 /**
  * An enum type to describe supported features of a certain manifest.
  */
-#[derive(Debug, Clone, Copy, serde::Serialize)]
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
 pub enum EFeatureLevel {
     // The original version.
     Original,
@@ -332,6 +405,27 @@ impl<const DIGEST_LENGTH:usize> serde::Serialize for UnknownHash<DIGEST_LENGTH>
     }
 }
 
+impl<'de, const DIGEST_LENGTH:usize> serde::Deserialize<'de> for UnknownHash<DIGEST_LENGTH> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let hex = String::deserialize(deserializer)?;
+        hex.parse().map_err(|_| serde::de::Error::invalid_value(serde::de::Unexpected::Str(&hex), &"a hexadecimal hash"))
+    }
+}
+
+impl<const DIGEST_LENGTH:usize> std::str::FromStr for UnknownHash<DIGEST_LENGTH> {
+    type Err = ParseError;
+
+    /// Parses a hash from its hexadecimal representation
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(UnknownHash {
+            data: helper::from_hex(s)?.try_into().map_err(|_| ParseError::InvalidData)?
+        })
+    }
+}
+
 impl<const DIGEST_LENGTH:usize> UnknownHash<DIGEST_LENGTH> {
     pub fn new(data: [u8; DIGEST_LENGTH]) -> UnknownHash<DIGEST_LENGTH> {
         UnknownHash {
@@ -383,6 +477,16 @@ impl serde::Serialize for FSHAHash {
     }
 }
 
+impl<'de> serde::Deserialize<'de> for FSHAHash {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let hex = String::deserialize(deserializer)?;
+        hex.parse().map_err(|_| serde::de::Error::invalid_value(serde::de::Unexpected::Str(&hex), &"a 40 characters hexadecimal SHA1 hash"))
+    }
+}
+
 impl Default for FSHAHash {
     fn default() -> Self {
         FSHAHash {
@@ -397,6 +501,17 @@ impl PartialEq for FSHAHash {
     }
 }
 
+impl std::str::FromStr for FSHAHash {
+    type Err = ParseError;
+
+    /// Parses a SHA1 hash from its hexadecimal representation
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(FSHAHash {
+            data: helper::from_hex(s)?.try_into().map_err(|_| ParseError::InvalidData)?
+        })
+    }
+}
+
 impl FSHAHash {
     pub fn new(data: [u8; SHA1_DIGEST_SIZE]) -> FSHAHash {
         FSHAHash {
@@ -404,7 +519,7 @@ impl FSHAHash {
         }
     }
 
-    fn to_string(&self) -> String {
+    pub(crate) fn to_string(&self) -> String {
         let mut result = String::with_capacity(SHA1_DIGEST_SIZE*2);
         for byte in self.data.iter() {
             result.push_str(&format!("{:02x}", byte));
@@ -441,3 +556,21 @@ impl FSHAHash {
         hasher.finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn storage_flags_are_read_from_their_bits() {
+        let flags = EManifestStorageFlags::try_from(0b11).unwrap();
+        assert!(flags.is_compressed() && flags.is_encrypted());
+        assert_eq!(flags.bits(), (EManifestStorageFlags::Compressed | EManifestStorageFlags::Encrypted).bits());
+        assert!(EManifestStorageFlags::try_from(0).unwrap().is_empty());
+        assert!(matches!(EManifestStorageFlags::try_from(0b100), Err(ParseError::InvalidStorageFlag)));
+
+        let flags = EChunkStorageFlags::try_from(0b10).unwrap();
+        assert!(!flags.is_compressed() && flags.is_encrypted());
+        assert!(matches!(EChunkStorageFlags::try_from(0b1000), Err(ParseError::InvalidStorageFlag)));
+    }
+}
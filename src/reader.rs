@@ -1,47 +1,70 @@
 // Define a struct to represent a byte reader
 // It will be used to parse the actual binary into a proper Manifest.
 
-use std::ffi::CString;
+use std::{borrow::Cow, ffi::CString};
 
 use widestring::U16String;
 
 use crate::{ error::ParseError, manifest::shared::{FGuid, FSHAHash, SHA1_DIGEST_SIZE}, ParseResult };
 
 #[derive(Debug)]
-pub struct ByteReader {
-    data: Vec<u8>,
+pub struct ByteReader<'a> {
+    data: Cow<'a, [u8]>,
     position: usize,
+    lenient: bool,
+    warnings: Vec<ParseError>,
 }
 
-impl ByteReader {
+impl ByteReader<'static> {
     /// Creates a new ByteReader from a Vec<u8>
     ///
     /// # Arguments
     ///
     /// * `data` - A Vec<u8> containing the binary data
     ///
-    pub fn new(data: Vec<u8>) -> ByteReader {
+    pub fn new(data: Vec<u8>) -> ByteReader<'static> {
         ByteReader {
-            data,
+            data: Cow::Owned(data),
             position: 0,
+            lenient: false,
+            warnings: vec![],
         }
     }
+}
 
-    /// This function is used to read a certain amount of bytes from the binary data and return it as a Vec<u8>
-    pub fn read_bytes(&mut self, size: usize) -> ParseResult<Vec<u8>> {
+impl<'a> ByteReader<'a> {
+    /// Creates a new ByteReader borrowing the binary data instead of copying it
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - A slice containing the binary data
+    ///
+    pub fn from_slice(data: &'a [u8]) -> ByteReader<'a> {
+        ByteReader {
+            data: Cow::Borrowed(data),
+            position: 0,
+            lenient: false,
+            warnings: vec![],
+        }
+    }
+
+    /// This function is used to borrow a certain amount of bytes from the binary data without copying them
+    pub fn read_slice(&mut self, size: usize) -> ParseResult<&[u8]> {
         if self.position + size > self.data.len() {
             return Err(ParseError::Overflow);
         }
 
-        let mut result = Vec::with_capacity(size);
-        for i in 0..size {
-            result.push(self.data[self.position + i]);
-        }
+        let result = &self.data[self.position..self.position + size];
         self.position += size;
 
         Ok(result)
     }
 
+    /// This function is used to read a certain amount of bytes from the binary data and return it as a Vec<u8>
+    pub fn read_bytes(&mut self, size: usize) -> ParseResult<Vec<u8>> {
+        self.read_slice(size).map(|bytes| bytes.to_vec())
+    }
+
     /// This function is used to read any type that implements the ByteReadable trait
     pub fn read<T: ByteReadable>(&mut self) -> ParseResult<T> {
         T::read(self)
@@ -57,10 +80,66 @@ impl ByteReader {
         self.data.len()
     }
 
+    /// This function is used to enable the lenient mode: sections that don't end where their stored size says they should
+    /// are recorded as warnings and skipped using their stored size, instead of failing the parse
+    pub fn set_lenient(&mut self, lenient: bool) {
+        self.lenient = lenient;
+    }
+
+    pub fn is_lenient(&self) -> bool {
+        self.lenient
+    }
+
+    /// This function is used to record a problem that didn't stop the parse
+    pub fn warn(&mut self, warning: ParseError) {
+        self.warnings.push(warning);
+    }
+
+    /// This function is used to get the warnings recorded so far, they are removed from the reader
+    pub fn take_warnings(&mut self) -> Vec<ParseError> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// This function is used at the end of a section to check that it was read entirely, according to the size stored at its beginning.
+    /// In lenient mode the reader is moved to the end of the section instead, so unknown trailing data is skipped.
+    pub fn end_section(&mut self, section: &'static str, start: usize, size: usize, data_version: Option<u32>) -> ParseResult<()> {
+        if self.position == start + size {
+            return Ok(());
+        }
+
+        let mismatch = ParseError::SectionSizeMismatch {
+            section,
+            offset: start,
+            expected: size,
+            actual: self.position - start,
+            data_version
+        };
+
+        if !self.lenient || start + size > self.data.len() {
+            return Err(mismatch);
+        }
+
+        self.warn(mismatch);
+        self.position = start + size;
+
+        Ok(())
+    }
+
     pub fn seek(&mut self, position: usize) {
         self.position = position;
     }
 
+    /// This function is used to read the element count of a list, each element taking at least `item_size` bytes.
+    /// A count that doesn't fit in the remaining data is rejected before anything is allocated for the list.
+    pub fn read_count(&mut self, item_size: usize) -> ParseResult<usize> {
+        let count = self.read::<u32>()? as usize;
+
+        match count.checked_mul(item_size) {
+            Some(size) if size <= self.length().saturating_sub(self.position) => Ok(count),
+            _ => Err(ParseError::Overflow)
+        }
+    }
+
     /// This function is used to read an array. It takes a closure that will be used to read each item of the array
     /// # Exemples (from src/manifest/meta.rs)
     /// ```
@@ -73,7 +152,7 @@ impl ByteReader {
         &mut self,
         mut read_item: impl FnMut(&mut Self) -> ParseResult<T>
     ) -> ParseResult<Vec<T>> {
-        let count = self.read::<u32>()? as usize;
+        let count = self.read_count(1)?;
 
         if count == 0 {
             return Ok(vec![]);
@@ -94,14 +173,14 @@ impl ByteReader {
 }
 
 pub trait ByteReadable: Sized {
-    fn read(reader: &mut ByteReader) -> ParseResult<Self>;
+    fn read(reader: &mut ByteReader<'_>) -> ParseResult<Self>;
 }
 
 impl ByteReadable for u64 {
     fn read(reader: &mut ByteReader) -> ParseResult<Self> {
         let result = u64::from_le_bytes(
             reader
-                .read_bytes(8)?
+                .read_slice(8)?
                 .try_into()
                 .map_err(|_| ParseError::InvalidData)?
         );
@@ -113,7 +192,7 @@ impl ByteReadable for u32 {
     fn read(reader: &mut ByteReader) -> ParseResult<Self> {
         let result = u32::from_le_bytes(
             reader
-                .read_bytes(4)?
+                .read_slice(4)?
                 .try_into()
                 .map_err(|_| ParseError::InvalidData)?
         );
@@ -125,7 +204,7 @@ impl ByteReadable for u16 {
     fn read(reader: &mut ByteReader) -> ParseResult<Self> {
         let result = u16::from_le_bytes(
             reader
-                .read_bytes(2)?
+                .read_slice(2)?
                 .try_into()
                 .map_err(|_| ParseError::InvalidData)?
         );
@@ -137,7 +216,7 @@ impl ByteReadable for u8 {
     fn read(reader: &mut ByteReader) -> ParseResult<Self> {
         let result = u8::from_le_bytes(
             reader
-                .read_bytes(1)?
+                .read_slice(1)?
                 .try_into()
                 .map_err(|_| ParseError::InvalidData)?
         );
@@ -149,7 +228,7 @@ impl ByteReadable for i64 {
     fn read(reader: &mut ByteReader) -> ParseResult<Self> {
         let result = i64::from_le_bytes(
             reader
-                .read_bytes(8)?
+                .read_slice(8)?
                 .try_into()
                 .map_err(|_| ParseError::InvalidData)?
         );
@@ -161,7 +240,7 @@ impl ByteReadable for i32 {
     fn read(reader: &mut ByteReader) -> ParseResult<Self> {
         let result = i32::from_le_bytes(
             reader
-                .read_bytes(4)?
+                .read_slice(4)?
                 .try_into()
                 .map_err(|_| ParseError::InvalidData)?
         );
@@ -173,7 +252,7 @@ impl ByteReadable for i16 {
     fn read(reader: &mut ByteReader) -> ParseResult<Self> {
         let result = i16::from_le_bytes(
             reader
-                .read_bytes(2)?
+                .read_slice(2)?
                 .try_into()
                 .map_err(|_| ParseError::InvalidData)?
         );
@@ -185,7 +264,7 @@ impl ByteReadable for i8 {
     fn read(reader: &mut ByteReader) -> ParseResult<Self> {
         let result = i8::from_le_bytes(
             reader
-                .read_bytes(1)?
+                .read_slice(1)?
                 .try_into()
                 .map_err(|_| ParseError::InvalidData)?
         );
@@ -210,17 +289,17 @@ impl ByteReadable for String {
 
             c_string.into_string().map_err(|_| ParseError::InvalidData)?
         } else {
-            let length = (length * -2) as usize;
-            let byte_data = reader.read_bytes(length)?;
-
-            //shouldn't panic
-            unsafe {
-                let u16_string = U16String::from_ptr(
-                    byte_data.as_ptr() as *const u16,
-                    length.abs_diff(0)
-                );
-                u16_string.to_string_lossy()
-            }
+            let length = length.unsigned_abs() as usize;
+            let byte_data = reader.read_bytes(length * 2)?;
+
+            //the stored length includes the null terminator, which we don't want in the String
+            let code_units = byte_data
+                .chunks_exact(2)
+                .map(|code_unit| u16::from_le_bytes([code_unit[0], code_unit[1]]))
+                .take_while(|code_unit| *code_unit != 0)
+                .collect::<Vec<u16>>();
+
+            U16String::from_vec(code_units).to_string_lossy()
         };
 
         Ok(string)
@@ -0,0 +1,203 @@
+// Rust implementation of FRollingHash from BuildPatchServices.
+// It is the RollingPoly64 hash stored in FChunkInfo and FChunkHeader.
+
+use std::collections::VecDeque;
+
+/// The polynomial used to generate the hash table (ECMA-182, reversed)
+pub const HASH_POLY_64: u64 = 0xC96C5795D7870F42;
+
+/// Lookup table used by the rolling hash, the same table is built by BuildPatchServices at startup
+pub const HASH_TABLE: [u64; 256] = build_hash_table();
+
+const fn build_hash_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut index = 0;
+
+    while index < 256 {
+        let mut value = index as u64;
+        let mut shift_count = 0;
+
+        while shift_count < 8 {
+            if value & 1 == 1 {
+                value = (value >> 1) ^ HASH_POLY_64;
+            } else {
+                value >>= 1;
+            }
+            shift_count += 1;
+        }
+
+        table[index] = value;
+        index += 1;
+    }
+
+    table
+}
+
+/// This type is used to compute the hash of a window of data, and to move that window forward one byte at a time
+#[derive(Debug, Clone)]
+pub struct FRollingHash {
+    window_size: u32,
+    hash_state: u64,
+    window_data: VecDeque<u8>,
+}
+
+impl FRollingHash {
+    /// Creates a new, empty rolling hash for a window of `window_size` bytes
+    pub fn new(window_size: u32) -> FRollingHash {
+        FRollingHash {
+            window_size,
+            hash_state: 0,
+            window_data: VecDeque::with_capacity(window_size as usize),
+        }
+    }
+
+    /// This function is used to compute the hash of a whole data set, this is the hash stored for chunks
+    pub fn get_hash_for_data_set(data: &[u8]) -> u64 {
+        data.iter().fold(0, |hash, byte| hash.rotate_left(1) ^ HASH_TABLE[*byte as usize])
+    }
+
+    /// This function is used to add a byte to the window while it isn't full yet
+    /// # Panics
+    /// Panics if the window is already full, use `roll_forward` instead
+    pub fn consume_byte(&mut self, byte: u8) {
+        assert!(self.num_data_needed() > 0, "the rolling hash window is already full");
+
+        self.hash_state = self.hash_state.rotate_left(1) ^ HASH_TABLE[byte as usize];
+        self.window_data.push_back(byte);
+    }
+
+    /// This function is used to add several bytes to the window while it isn't full yet
+    pub fn consume_bytes(&mut self, data: &[u8]) {
+        for byte in data {
+            self.consume_byte(*byte);
+        }
+    }
+
+    /// This function is used to get the amount of bytes that still have to be consumed before the window is full
+    pub fn num_data_needed(&self) -> u32 {
+        self.window_size - self.window_data.len() as u32
+    }
+
+    /// This function is used to move the window forward by one byte, dropping the oldest byte of the window
+    /// # Panics
+    /// Panics if the window is not full yet
+    pub fn roll_forward(&mut self, byte: u8) {
+        assert!(self.num_data_needed() == 0, "the rolling hash window must be full before rolling forward");
+
+        let old_byte = self.window_data.pop_front().unwrap_or_default();
+        self.window_data.push_back(byte);
+
+        let old_byte_hash = HASH_TABLE[old_byte as usize].rotate_left(self.window_size);
+        self.hash_state = self.hash_state.rotate_left(1) ^ old_byte_hash ^ HASH_TABLE[byte as usize];
+    }
+
+    /// This function is used to get the hash of the current window
+    pub fn get_window_hash(&self) -> u64 {
+        self.hash_state
+    }
+
+    /// This function is used to get the bytes of the current window, oldest byte first
+    pub fn get_window_data(&self) -> impl Iterator<Item = &u8> {
+        self.window_data.iter()
+    }
+
+    pub fn window_size(&self) -> u32 {
+        self.window_size
+    }
+
+    /// This function is used to reset the rolling hash to its initial state
+    pub fn clear(&mut self) {
+        self.hash_state = 0;
+        self.window_data.clear();
+    }
+
+    /// This function is used to iterate over the hash of every window of `window_size` bytes in `data`.
+    /// Items are the offset of the window in `data` and its hash, which can be looked up in a list of chunk hashes.
+    pub fn windows(data: &[u8], window_size: u32) -> RollingHashWindows<'_> {
+        RollingHashWindows {
+            data,
+            offset: 0,
+            hash: FRollingHash::new(window_size),
+        }
+    }
+}
+
+/// Iterator returned by `FRollingHash::windows`
+pub struct RollingHashWindows<'a> {
+    data: &'a [u8],
+    offset: usize,
+    hash: FRollingHash,
+}
+
+impl Iterator for RollingHashWindows<'_> {
+    type Item = (usize, u64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let window_size = self.hash.window_size() as usize;
+
+        if window_size == 0 || self.offset + window_size > self.data.len() {
+            return None;
+        }
+
+        if self.hash.num_data_needed() > 0 {
+            self.hash.consume_bytes(&self.data[..window_size]);
+        } else {
+            self.hash.roll_forward(self.data[self.offset + window_size - 1]);
+        }
+
+        let item = (self.offset, self.hash.get_window_hash());
+        self.offset += 1;
+
+        Some(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::random_data;
+
+    use super::*;
+
+    #[test]
+    fn hash_table_matches_known_values() {
+        assert_eq!(HASH_TABLE[0], 0);
+        assert_eq!(HASH_TABLE[1], 0xB32E4CBE03A75F6F);
+        assert_eq!(HASH_TABLE[2], 0xF4843657A840A05B);
+        assert_eq!(HASH_TABLE[128], HASH_POLY_64);
+        assert_eq!(HASH_TABLE[255], 0xE0ADA17364673F59);
+    }
+
+    #[test]
+    fn data_set_hash_matches_known_values() {
+        assert_eq!(FRollingHash::get_hash_for_data_set(b""), 0);
+        assert_eq!(FRollingHash::get_hash_for_data_set(b"a"), 0x2CAF25044A02145C);
+        assert_eq!(FRollingHash::get_hash_for_data_set(b"123456789"), 0x1961F39C973D44D5);
+        assert_eq!(FRollingHash::get_hash_for_data_set(b"The quick brown fox jumps over the lazy dog"), 0x562894A34481E908);
+    }
+
+    #[test]
+    fn rolled_hash_matches_known_values() {
+        let mut hash = FRollingHash::new(4);
+        hash.consume_bytes(b"1234");
+        assert_eq!(hash.get_window_hash(), 0x1BC73844567DD897);
+
+        hash.roll_forward(b'5');
+        assert_eq!(hash.get_window_hash(), 0x21F6E395ACA941EA);
+
+        let windows: Vec<(usize, u64)> = FRollingHash::windows(b"123456789", 4).collect();
+        assert_eq!(windows.len(), 6);
+        assert_eq!(windows[5], (5, 0x84A03C36AF5CEE42));
+    }
+
+    #[test]
+    fn rolled_hash_matches_hash_of_the_window() {
+        let data = random_data(1000, 11);
+
+        // window sizes over 64 rotate by more than the width of the hash
+        for window_size in [1, 7, 64, 100] {
+            for (offset, hash) in FRollingHash::windows(&data, window_size) {
+                assert_eq!(hash, FRollingHash::get_hash_for_data_set(&data[offset..offset + window_size as usize]), "window of {} at {}", window_size, offset);
+            }
+        }
+    }
+}
@@ -0,0 +1,124 @@
+// Define a struct to represent a byte writer
+// It is the counterpart of the ByteReader and is used to serialize a Manifest back into its binary form.
+
+use crate::manifest::shared::{FGuid, FSHAHash, UnknownHash};
+
+#[derive(Debug, Default)]
+pub struct ByteWriter {
+    data: Vec<u8>,
+}
+
+impl ByteWriter {
+    /// Creates a new, empty ByteWriter
+    pub fn new() -> ByteWriter {
+        ByteWriter {
+            data: Vec::new(),
+        }
+    }
+
+    /// This function is used to append raw bytes to the binary data
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    /// This function is used to write any type that implements the ByteWritable trait
+    pub fn write<T: ByteWritable + ?Sized>(&mut self, value: &T) {
+        value.write(self)
+    }
+
+    /// This function is used to get the current position of the writer
+    pub fn tell(&self) -> usize {
+        self.data.len()
+    }
+
+    /// This function is used to overwrite an already written u32, it is mostly used to fill the size of a section once it has been serialized
+    pub fn write_u32_at(&mut self, position: usize, value: u32) {
+        self.data[position..position + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// This function is used to write an array. It takes a closure that will be used to write each item of the array
+    /// # Exemples (from src/manifest/meta.rs)
+    /// ```ignore
+    /// writer.write_array(&self.prereq_ids, |writer, id| writer.write(id));
+    /// ```
+    pub fn write_array<T>(&mut self, items: &[T], mut write_item: impl FnMut(&mut Self, &T)) {
+        self.write(&(items.len() as u32));
+
+        for item in items {
+            write_item(self, item);
+        }
+    }
+
+    /// This function is used to get the binary data that was written
+    pub fn into_inner(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+pub trait ByteWritable {
+    fn write(&self, writer: &mut ByteWriter);
+}
+
+macro_rules! impl_byte_writable_num {
+    ($($ty:ty),*) => {
+        $(
+            impl ByteWritable for $ty {
+                fn write(&self, writer: &mut ByteWriter) {
+                    writer.write_bytes(&self.to_le_bytes());
+                }
+            }
+        )*
+    };
+}
+
+impl_byte_writable_num!(u64, u32, u16, u8, i64, i32, i16, i8);
+
+impl ByteWritable for str {
+    fn write(&self, writer: &mut ByteWriter) {
+        if self.is_empty() {
+            writer.write(&0i32);
+            return;
+        }
+
+        // Unreal stores pure ANSI strings as a null terminated byte string and everything else as a null terminated UTF-16 string with a negative length
+        if self.is_ascii() {
+            writer.write(&(self.len() as i32 + 1));
+            writer.write_bytes(self.as_bytes());
+            writer.write(&0u8);
+        } else {
+            let utf_16 = self.encode_utf16().collect::<Vec<u16>>();
+            writer.write(&-(utf_16.len() as i32 + 1));
+            for code_unit in utf_16 {
+                writer.write(&code_unit);
+            }
+            writer.write(&0u16);
+        }
+    }
+}
+
+impl ByteWritable for String {
+    fn write(&self, writer: &mut ByteWriter) {
+        self.as_str().write(writer)
+    }
+}
+
+impl ByteWritable for FGuid {
+    fn write(&self, writer: &mut ByteWriter) {
+        writer.write(&self.a);
+        writer.write(&self.b);
+        writer.write(&self.c);
+        writer.write(&self.d);
+    }
+}
+
+impl ByteWritable for FSHAHash {
+    fn write(&self, writer: &mut ByteWriter) {
+        writer.write_bytes(&self.data);
+    }
+}
+
+impl<const DIGEST_LENGTH: usize> ByteWritable for UnknownHash<DIGEST_LENGTH> {
+    fn write(&self, writer: &mut ByteWriter) {
+        writer.write_bytes(&self.data);
+    }
+}